@@ -0,0 +1,203 @@
+use std::io::{self, Write};
+use crate::Instruction;
+use crate::program::Program;
+
+/// Writes programs back out as brainfuck, with a consistent layout.
+///
+/// Comments are dropped, and the commands are laid out in lines of at most
+/// the chosen width. Loops which hold other loops, or don't fit on the
+/// line, are written with their brackets on lines of their own, and their
+/// body indented. Other loops are kept on one line with the commands around
+/// them.
+///
+/// ```
+/// use brainfuck::codegen::Brainfuck;
+/// use brainfuck::program::Program;
+///
+/// let program = Program::parse("Copy ++[>+[-]<-] done").unwrap();
+/// assert_eq!(Brainfuck::default().transpile(&program), "\
+/// ++
+/// [
+///     >+[-]<-
+/// ]
+/// ");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Brainfuck {
+    indent: usize,
+    width: usize,
+}
+
+impl Default for Brainfuck {
+    fn default() -> Self {
+        Brainfuck {
+            indent: 4,
+            width: 80,
+        }
+    }
+}
+
+/// A part of a program, either a run of commands without brackets, or a
+/// loop.
+enum Node {
+    Commands(String),
+    Loop(Vec<Node>),
+}
+
+impl Node {
+    /// The commands of the node, if it has no loops inside it.
+    fn flat(&self) -> Option<String> {
+        match *self {
+            Node::Commands(ref s) => Some(s.clone()),
+            Node::Loop(ref body) => {
+                let mut text = String::from("[");
+                for node in body {
+                    match *node {
+                        Node::Commands(ref s) => text.push_str(s),
+                        Node::Loop(_) => return None,
+                    }
+                }
+                text.push(']');
+                Some(text)
+            },
+        }
+    }
+}
+
+impl Brainfuck {
+    /// Indent the bodies of loops by the given number of spaces, `4` by
+    /// default.
+    pub fn indent(&mut self, spaces: usize) -> &mut Self {
+        self.indent = spaces;
+        self
+    }
+
+    /// Keep lines to the given number of characters where possible, `80` by
+    /// default. Lines are never shorter than 8 commands, however deep the
+    /// loops go.
+    pub fn width(&mut self, width: usize) -> &mut Self {
+        self.width = width;
+        self
+    }
+
+    /// Format the program, returning the brainfuck source.
+    pub fn transpile(&self, program: &Program) -> String {
+        let mut source = Vec::new();
+        self.write(program, &mut source).expect("writing to a Vec");
+        String::from_utf8(source).expect("brainfuck is ASCII")
+    }
+
+    /// Format the program, writing the brainfuck source to the given writer.
+    pub fn write<W: Write>(&self, program: &Program, out: &mut W) -> io::Result<()> {
+        let mut lines = Vec::new();
+        self.layout(&tree(program), 0, &mut lines);
+        for line in lines {
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Lay out the nodes at the given depth of loops, adding the lines.
+    fn layout(&self, nodes: &[Node], depth: usize, lines: &mut Vec<String>) {
+        let margin = " ".repeat(depth * self.indent);
+        let room = self.width.saturating_sub(margin.len()).max(8);
+        let mut line = String::new();
+        for node in nodes {
+            match node.flat() {
+                Some(ref text) if text.starts_with('[') && text.len() <= room => {
+                    // Loops aren't split across lines.
+                    if line.len() + text.len() > room {
+                        lines.push(format!("{}{}", margin, line));
+                        line.clear();
+                    }
+                    line.push_str(text);
+                },
+                Some(ref text) if !text.starts_with('[') => {
+                    for c in text.chars() {
+                        if line.len() == room {
+                            lines.push(format!("{}{}", margin, line));
+                            line.clear();
+                        }
+                        line.push(c);
+                    }
+                },
+                _ => {
+                    if !line.is_empty() {
+                        lines.push(format!("{}{}", margin, line));
+                        line.clear();
+                    }
+                    lines.push(format!("{}[", margin));
+                    if let Node::Loop(ref body) = *node {
+                        self.layout(body, depth + 1, lines);
+                    }
+                    lines.push(format!("{}]", margin));
+                },
+            }
+        }
+        if !line.is_empty() {
+            lines.push(format!("{}{}", margin, line));
+        }
+    }
+}
+
+/// The program as a tree of nodes.
+fn tree(program: &Program) -> Vec<Node> {
+    // The nodes of each loop being built, outermost first.
+    let mut stack = vec![Vec::new()];
+    for pc in 0..program.len() {
+        let instruction = program.get(pc).expect("in");
+        match instruction {
+            Instruction::SkipForward(_) => stack.push(Vec::new()),
+            Instruction::SkipBackward(_) => {
+                let body = stack.pop().expect("matched brackets");
+                stack.last_mut().expect("matched brackets").push(Node::Loop(body));
+            },
+            _ => {
+                let nodes = stack.last_mut().expect("matched brackets");
+                if let Some(Node::Commands(ref mut s)) = nodes.last_mut() {
+                    s.push_str(&instruction.to_string());
+                } else {
+                    nodes.push(Node::Commands(instruction.to_string()));
+                }
+            },
+        }
+    }
+    stack.pop().expect("matched brackets")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str, width: usize) -> String {
+        Brainfuck::default().width(width).transpile(&Program::parse(source).unwrap())
+    }
+
+    #[test]
+    fn flat() {
+        assert_eq!(format("hello +++ world [-]>.", 80), "+++[-]>.\n");
+        assert_eq!(format("", 80), "");
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(format("+[>[-]<[>+<-]]>.", 80), "+\n[\n    >[-]<[>+<-]\n]\n>.\n");
+        assert_eq!(format("[[[]]]", 80), "[\n    [\n        []\n    ]\n]\n");
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(format("++++++++++++", 8), "++++++++\n++++\n");
+        // A loop moves to the next line rather than being split.
+        assert_eq!(format("++++++[->+<]", 8), "++++++\n[->+<]\n");
+        // A loop too long for a line of its own is laid out as a block.
+        assert_eq!(format("[>>>>>>>>>]", 8), "[\n    >>>>>>>>\n    >\n]\n");
+    }
+
+    #[test]
+    fn round_trip() {
+        let program = Program::from_file("fixtures/dbfi.b").unwrap();
+        let formatted = Brainfuck::default().transpile(&program);
+        assert_eq!(Program::parse(&formatted).unwrap().to_string(), program.to_string());
+    }
+}
@@ -0,0 +1,241 @@
+use std::io::{self, Write};
+use crate::Eof;
+use crate::ir::{Ir, Op};
+use crate::program::Program;
+use crate::tape::TAPE_LENGTH;
+
+/// Transpiles programs to standalone, portable C.
+///
+/// The generated C only depends on the standard library, and reads and
+/// writes `stdin` and `stdout`. It's built from the optimized [`Ir`][ir] of
+/// the program, so the loops the IR replaces become single statements.
+///
+/// The semantics follow the options set on the transpiler. Cells are
+/// unsigned integers of the chosen width, which wrap on overflow like
+/// `ModArrayTape`. The tape has the chosen length, and the pointer wraps
+/// around at its ends, also like `ModArrayTape`. Reading past the end of the
+/// input follows the chosen `Eof` convention, where `Eof::Error` ends the
+/// program with an error and an exit status of `1`.
+///
+/// ```
+/// use brainfuck::codegen::C;
+/// use brainfuck::program::Program;
+///
+/// let program = Program::parse("++>+.").unwrap();
+/// let source = C::default().cell_width(16).transpile(&program);
+/// assert!(source.contains("uint16_t"));
+/// ```
+///
+/// [ir]: ../ir/struct.Ir.html
+#[derive(Clone, Debug)]
+pub struct C {
+    tape_length: usize,
+    cell_width: u32,
+    eof: Eof,
+}
+
+impl Default for C {
+    fn default() -> Self {
+        C {
+            tape_length: TAPE_LENGTH,
+            cell_width: 8,
+            eof: Eof::default(),
+        }
+    }
+}
+
+impl C {
+    /// Use a tape with the given number of cells, `TAPE_LENGTH` by default.
+    /// There must be at least one.
+    pub fn tape_length(&mut self, length: usize) -> &mut Self {
+        assert!(length > 0, "a tape needs a cell");
+        self.tape_length = length;
+        self
+    }
+
+    /// Use cells with the given number of bits, `8` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `bits` is one of `8`, `16`, `32` or `64`.
+    pub fn cell_width(&mut self, bits: u32) -> &mut Self {
+        assert!([8, 16, 32, 64].contains(&bits), "Unsupported cell width {}", bits);
+        self.cell_width = bits;
+        self
+    }
+
+    /// Use the given convention for reading past the end of the input.
+    pub fn on_eof(&mut self, eof: Eof) -> &mut Self {
+        self.eof = eof;
+        self
+    }
+
+    /// Transpile the program, returning the C source.
+    pub fn transpile(&self, program: &Program) -> String {
+        let mut source = Vec::new();
+        self.write(program, &mut source).expect("writing to a Vec");
+        String::from_utf8(source).expect("generated C is ASCII")
+    }
+
+    /// Transpile the program, writing the C source to the given writer.
+    pub fn write<W: Write>(&self, program: &Program, out: &mut W) -> io::Result<()> {
+        let ir = Ir::wrapping(program, self.tape_length);
+        writeln!(out, "#include <stdint.h>")?;
+        writeln!(out, "#include <stdio.h>")?;
+        writeln!(out, "#include <stdlib.h>")?;
+        writeln!(out)?;
+        writeln!(out, "typedef uint{}_t cell;", self.cell_width)?;
+        writeln!(out)?;
+        writeln!(out, "static cell t[{}];", self.tape_length)?;
+        writeln!(out)?;
+        if self.eof == Eof::Error {
+            writeln!(out, "static void fail(const char *message) {{")?;
+            writeln!(out, "    fflush(stdout);")?;
+            writeln!(out, "    fprintf(stderr, \"%s\\n\", message);")?;
+            writeln!(out, "    exit(1);")?;
+            writeln!(out, "}}")?;
+            writeln!(out)?;
+        }
+        // Moves are all less than the length of the tape, see `offset`.
+        writeln!(out, "static long wrap(long i) {{")?;
+        writeln!(out, "    if (i < 0) return i + {};", self.tape_length)?;
+        writeln!(out, "    if (i >= {0}) return i - {0};", self.tape_length)?;
+        writeln!(out, "    return i;")?;
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out, "int main(void) {{")?;
+        writeln!(out, "    long i = 0;")?;
+        if (0..ir.len()).any(|i| ir.get(i) == Some(Op::Input)) {
+            writeln!(out, "    int c;")?;
+        }
+        let mut depth = 1;
+        for index in 0..ir.len() {
+            let op = ir.get(index).expect("in");
+            if let Op::JumpBackward(_) = op {
+                depth -= 1;
+            }
+            write!(out, "{:1$}", "", depth * 4)?;
+            match op {
+                Op::AddVal(n) if n < 0 => writeln!(out, "t[i] -= {};", -n)?,
+                Op::AddVal(n) => writeln!(out, "t[i] += {};", n)?,
+                Op::MovePtr(n) => writeln!(out, "i = wrap({});", self.offset(n))?,
+                Op::Output => writeln!(out, "putchar((unsigned char)t[i]);")?,
+                Op::Input => match self.eof_statement() {
+                    Some(s) => writeln!(out, "if ((c = getchar()) != EOF) t[i] = c; else {}", s)?,
+                    None => writeln!(out, "if ((c = getchar()) != EOF) t[i] = c;")?,
+                },
+                Op::JumpForward(_) => {
+                    writeln!(out, "while (t[i]) {{")?;
+                    depth += 1;
+                },
+                Op::JumpBackward(_) => writeln!(out, "}}")?,
+                Op::SetZero => writeln!(out, "t[i] = 0;")?,
+                // Narrow cells are promoted to `int`, where the product
+                // could overflow, so it's taken in the widest unsigned type.
+                Op::MulAdd(offset, factor) => {
+                    writeln!(out, "if (t[i]) t[wrap({})] += (cell)((uintmax_t)t[i] * (cell){});",
+                             self.offset(offset), factor)?
+                },
+                Op::ScanRight(n) => writeln!(out, "while (t[i]) i = wrap({});", self.offset(n as isize))?,
+                Op::ScanLeft(n) => writeln!(out, "while (t[i]) i = wrap({});", self.offset(-(n as isize)))?,
+            }
+        }
+        writeln!(out, "    return 0;")?;
+        writeln!(out, "}}")
+    }
+
+    /// The index `n` cells from `i`, before wrapping around. Whole trips
+    /// around the tape are left out, so `wrap` only needs to go around once.
+    fn offset(&self, n: isize) -> String {
+        match n % self.tape_length as isize {
+            n if n < 0 => format!("i - {}", -n),
+            n => format!("i + {}", n),
+        }
+    }
+
+    /// The statement run when reading past the end of the input.
+    fn eof_statement(&self) -> Option<&'static str> {
+        match self.eof {
+            Eof::Unchanged => None,
+            Eof::Zero => Some("t[i] = 0;"),
+            Eof::AllOnes => Some("t[i] = (cell)-1;"),
+            Eof::Error => Some("fail(\"Read past the end of input\");"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transpile(source: &str) -> String {
+        C::default().transpile(&Program::parse(source).unwrap())
+    }
+
+    #[test]
+    fn empty() {
+        let source = transpile("");
+        assert!(source.contains("typedef uint8_t cell;"));
+        assert!(source.contains("static cell t[30000];"));
+        assert!(source.ends_with("    long i = 0;\n    return 0;\n}\n"));
+    }
+
+    #[test]
+    fn ops() {
+        let source = transpile("+++>--<.,[-]");
+        assert!(source.contains("    t[i] += 3;\n    i = wrap(i + 1);\n    t[i] -= 2;\n"));
+        assert!(source.contains("i = wrap(i - 1);"));
+        assert!(source.contains("putchar((unsigned char)t[i]);"));
+        assert!(source.contains("if ((c = getchar()) != EOF) t[i] = c;\n"));
+        assert!(source.contains("t[i] = 0;"));
+    }
+
+    #[test]
+    fn loops() {
+        let source = transpile("+[>[->++<]<-]");
+        assert!(source.contains("    while (t[i]) {\n        i = wrap(i + 1);\n        if (t[i]) t[wrap(i + 1)] += (cell)((uintmax_t)t[i] * (cell)2);\n        t[i] = 0;\n"));
+        assert!(source.contains("        t[i] -= 1;\n    }\n"));
+    }
+
+    #[test]
+    fn options() {
+        let program = Program::parse(",").unwrap();
+        let source = C::default()
+            .tape_length(100)
+            .cell_width(32)
+            .on_eof(Eof::AllOnes)
+            .transpile(&program);
+        assert!(source.contains("typedef uint32_t cell;"));
+        assert!(source.contains("static cell t[100];"));
+        assert!(source.contains("else t[i] = (cell)-1;"));
+    }
+
+    #[test]
+    fn wrapping() {
+        let program = Program::parse("<[>>>>>]+[->>>>>>+<<<<<<]").unwrap();
+        let source = C::default().tape_length(4).transpile(&program);
+        assert!(source.contains("    if (i < 0) return i + 4;\n    if (i >= 4) return i - 4;\n"));
+        assert!(source.contains("    i = wrap(i - 1);\n    while (t[i]) i = wrap(i + 1);\n"));
+        assert!(source.contains("t[wrap(i + 2)] += "));
+        // Going around by 4 lands on the counter, so that loop is kept.
+        let source = C::default().tape_length(3).transpile(&program);
+        assert!(source.contains("while (t[i]) i = wrap(i + 2);\n"));
+        assert!(source.contains("    while (t[i]) {\n        t[i] -= 1;\n        i = wrap(i + 0);\n"));
+    }
+
+    #[test]
+    fn wide_products() {
+        // A `uint16_t` product would be taken in `int`, and could overflow.
+        let program = Program::parse("[->+++<]<[->>-<<]").unwrap();
+        let source = C::default().cell_width(16).transpile(&program);
+        assert!(source.contains("typedef uint16_t cell;"));
+        assert!(source.contains("if (t[i]) t[wrap(i + 1)] += (cell)((uintmax_t)t[i] * (cell)3);\n"));
+        assert!(source.contains("if (t[i]) t[wrap(i + 2)] += (cell)((uintmax_t)t[i] * (cell)-1);\n"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn bad_cell_width() {
+        C::default().cell_width(12);
+    }
+}
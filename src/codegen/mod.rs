@@ -0,0 +1,9 @@
+// Re-exports.
+pub use self::brainfuck::Brainfuck;
+pub use self::c::C;
+
+/// A brainfuck backend, for formatting programs.
+mod brainfuck;
+
+/// A C backend.
+mod c;
@@ -0,0 +1,156 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::program::Position;
+
+/// A place to stop the program, before executing an instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stop at the instruction with the given program counter.
+    Pc(usize),
+    /// Stop at the first instruction at or after the given position in the
+    /// source.
+    Position(Position),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Breakpoint::Pc(pc) => write!(f, "{}", pc),
+            Breakpoint::Position(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    /// Parse a breakpoint from a program counter like `12`, or a position
+    /// like `3:4`.
+    fn from_str(s: &str) -> Result<Breakpoint, String> {
+        let invalid = || format!("Invalid breakpoint `{}`", s);
+        match s.find(':') {
+            Some(i) => {
+                let line = s[..i].parse().map_err(|_| invalid())?;
+                let column = s[i + 1..].parse().map_err(|_| invalid())?;
+                if line == 0 || column == 0 {
+                    return Err(invalid())
+                }
+                Ok(Breakpoint::Position(Position { line, column }))
+            },
+            None => s.parse().map(Breakpoint::Pc).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// A debugger command, parsed from a line like `break 3:4`.
+///
+/// Each command has a one letter short form, given in the `HELP` text.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Execute the given number of instructions.
+    Step(u64),
+    /// Execute the instruction, or the whole loop when at a `[`.
+    Next,
+    /// Execute until a breakpoint, or the end of the program.
+    Continue,
+    /// Add a breakpoint, or list them when `None`.
+    Break(Option<Breakpoint>),
+    /// Remove a breakpoint.
+    Delete(Breakpoint),
+    /// Show where the program is stopped.
+    Where,
+    /// Show the cells within the given distance of the pointer.
+    Tape(usize),
+    /// Show the cell at the given index, or at the pointer when `None`.
+    Print(Option<usize>),
+    /// Set the cell at the given index to the given value.
+    Set(usize, i128),
+    /// Show the `HELP` text.
+    Help,
+    /// Stop debugging.
+    Quit,
+}
+
+/// The commands the debugger understands.
+pub const HELP: &str = "\
+step [n]          s  Execute n instructions, 1 by default.
+next              n  Execute the instruction, or the whole loop at a `[`.
+continue          c  Execute until a breakpoint or the end.
+break [at]        b  Add a breakpoint at a pc or line:column, or list them.
+delete <at>       d  Remove a breakpoint.
+where             w  Show where the program is stopped.
+tape [radius]     t  Show the cells around the pointer, 8 by default.
+print [index]     p  Show a cell, the current one by default.
+set <index> <v>      Set a cell to a value.
+help              h  Show this help.
+quit              q  Stop debugging.
+";
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Command, String> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Err("Empty command".into()),
+        };
+        fn number<N: FromStr>(s: &str) -> Result<N, String> {
+            s.parse().map_err(|_| format!("Invalid number `{}`", s))
+        }
+        let command = match (name, args) {
+            ("step" | "s", []) => Command::Step(1),
+            ("step" | "s", [n]) => match number(n)? {
+                0 => return Err(format!("Invalid number of steps `{}`", n)),
+                n => Command::Step(n),
+            },
+            ("next" | "n", []) => Command::Next,
+            ("continue" | "c", []) => Command::Continue,
+            ("break" | "b", []) => Command::Break(None),
+            ("break" | "b", [at]) => Command::Break(Some(at.parse()?)),
+            ("delete" | "d", [at]) => Command::Delete(at.parse()?),
+            ("where" | "w", []) => Command::Where,
+            ("tape" | "t", []) => Command::Tape(8),
+            ("tape" | "t", [r]) => Command::Tape(number(r)?),
+            ("print" | "p", []) => Command::Print(None),
+            ("print" | "p", [i]) => Command::Print(Some(number(i)?)),
+            ("set", [i, v]) => Command::Set(number(i)?, number(v)?),
+            ("help" | "h", []) => Command::Help,
+            ("quit" | "q", []) => Command::Quit,
+            _ => return Err(format!("Unknown command `{}`, try `help`", s.trim())),
+        };
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breakpoint() {
+        assert_eq!("12".parse(), Ok(Breakpoint::Pc(12)));
+        assert_eq!("3:4".parse(), Ok(Breakpoint::Position(Position { line: 3, column: 4 })));
+        assert!("0:4".parse::<Breakpoint>().is_err());
+        assert!("x".parse::<Breakpoint>().is_err());
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!("s".parse(), Ok(Command::Step(1)));
+        assert_eq!("step 10".parse(), Ok(Command::Step(10)));
+        assert_eq!(" b  2:1 ".parse(), Ok(Command::Break(Some(Breakpoint::Position(Position { line: 2, column: 1 })))));
+        assert_eq!("t".parse(), Ok(Command::Tape(8)));
+        assert_eq!("set 3 -1".parse(), Ok(Command::Set(3, -1)));
+        assert_eq!("q".parse(), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn bad_commands() {
+        assert!("".parse::<Command>().is_err());
+        assert!("set 3 x".parse::<Command>().is_err());
+        assert!("next 2".parse::<Command>().is_err());
+        assert_eq!("step 0".parse::<Command>(), Err("Invalid number of steps `0`".into()));
+        assert!("jump".parse::<Command>().is_err());
+    }
+}
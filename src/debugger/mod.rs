@@ -0,0 +1,287 @@
+use std::io::{self, Write};
+use crate::{Instruction, Interpreter, Status};
+use crate::tape::{Cell, Tape};
+
+// Re-exports.
+pub use self::command::{Breakpoint, Command, HELP};
+
+/// An interactive debugger, driving an interpreter one step at a time.
+///
+/// The debugger executes [`Command`][command]s, writing what it has to say
+/// to a given writer, which is kept apart from the writer of the program
+/// itself. Reading commands is left to the caller, so the program and the
+/// debugger can share an input.
+///
+/// ```
+/// use brainfuck::Interpreter;
+/// use brainfuck::debugger::{Command, Debugger};
+/// use brainfuck::program::Program;
+/// use brainfuck::tape::VecTape;
+///
+/// let mut interp = Interpreter::<VecTape>::default();
+/// interp.load(Program::parse("+++[>+<-]").unwrap());
+/// let mut debugger = Debugger::new(interp);
+/// let mut out = Vec::new();
+/// for line in &["step 3", "next", "print 1"] {
+///     debugger.execute(line.parse().unwrap(), &mut out).unwrap();
+/// }
+/// assert!(String::from_utf8(out).unwrap().ends_with("cell 1 = 3\n"));
+/// ```
+///
+/// [command]: enum.Command.html
+pub struct Debugger<'a, T: Tape> {
+    interp: Interpreter<'a, T>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a, T: Tape + Default> Debugger<'a, T> {
+    /// Create a debugger for the given interpreter, stopped before its
+    /// next instruction.
+    pub fn new(interp: Interpreter<'a, T>) -> Debugger<'a, T> {
+        Debugger {
+            interp,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// The interpreter being debugged.
+    pub fn interpreter(&self) -> &Interpreter<'a, T> {
+        &self.interp
+    }
+
+    /// Execute a command, writing the result to `out`. Returns false once
+    /// the command is `Quit`.
+    pub fn execute<W: Write>(&mut self, command: Command, out: &mut W) -> io::Result<bool> {
+        match command {
+            Command::Step(n) => self.resume(out, Some(n), None)?,
+            Command::Next => {
+                let close = match self.current() {
+                    Some(Instruction::SkipForward(close)) => Some(close + 1),
+                    _ => None,
+                };
+                self.resume(out, close.map_or(Some(1), |_| None), close)?
+            },
+            Command::Continue => self.resume(out, None, None)?,
+            Command::Break(None) => {
+                for breakpoint in &self.breakpoints {
+                    writeln!(out, "{}", breakpoint)?;
+                }
+            },
+            Command::Break(Some(breakpoint)) => {
+                match self.resolve(breakpoint) {
+                    Some(pc) => {
+                        if !self.breakpoints.contains(&breakpoint) {
+                            self.breakpoints.push(breakpoint);
+                        }
+                        writeln!(out, "Breakpoint {} at {}", breakpoint, self.describe(pc))?;
+                    },
+                    None => writeln!(out, "No instruction at {}", breakpoint)?,
+                }
+            },
+            Command::Delete(breakpoint) => {
+                match self.breakpoints.iter().position(|b| *b == breakpoint) {
+                    Some(i) => {
+                        self.breakpoints.remove(i);
+                    },
+                    None => writeln!(out, "No breakpoint {}", breakpoint)?,
+                }
+            },
+            Command::Where => self.show(out)?,
+            Command::Tape(radius) => writeln!(out, "{}", window(self.interp.tape(), radius))?,
+            Command::Print(index) => {
+                let index = index.unwrap_or_else(|| self.interp.tape().ptr());
+                match self.interp.tape().get(index) {
+                    Some(v) => writeln!(out, "cell {} = {}", index, v)?,
+                    None => writeln!(out, "No cell {}", index)?,
+                }
+            },
+            Command::Set(index, value) => {
+                let value = match T::Cell::from_i128(value) {
+                    Some(v) => v,
+                    None => return writeln!(out, "Value {} doesn't fit in a cell", value).map(|_| true),
+                };
+                if let Err(e) = self.interp.tape_mut().set(index, value) {
+                    writeln!(out, "{}", e)?;
+                }
+            },
+            Command::Help => write!(out, "{}", HELP)?,
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Step until `steps` instructions have run, the program counter is
+    /// `until`, a breakpoint is reached, or the program ends or fails.
+    fn resume<W: Write>(&mut self, out: &mut W, steps: Option<u64>, until: Option<usize>) -> io::Result<()> {
+        if steps == Some(0) {
+            return self.show(out)
+        }
+        let mut count = 0;
+        loop {
+            match self.interp.step() {
+                Status::Running => count += 1,
+                Status::Halted => {
+                    return writeln!(out, "Finished after {} cycles", self.interp.cycles())
+                },
+                Status::Error(e) => {
+                    writeln!(out, "Error: {}", e.cause())?;
+                    return self.show(out)
+                },
+            }
+            let pc = self.interp.pc();
+            if Some(count) == steps || Some(pc) == until {
+                return self.show(out)
+            }
+            if self.breakpoints.iter().any(|b| self.resolve(*b) == Some(pc)) {
+                write!(out, "Breakpoint, ")?;
+                return self.show(out)
+            }
+        }
+    }
+
+    /// Show where the program is stopped, with the comment there.
+    fn show<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let pc = self.interp.pc();
+        if self.current().is_none() {
+            return writeln!(out, "at the end of the program")
+        }
+        writeln!(out, "at {}", self.describe(pc))?;
+        if let Some(comment) = self.interp.program().and_then(|p| p.comment(pc)) {
+            writeln!(out, "    {}", comment)?;
+        }
+        Ok(())
+    }
+
+    /// Describe the instruction at the given program counter.
+    fn describe(&self, pc: usize) -> String {
+        let program = self.interp.program().expect("loaded");
+        format!("{} ({}) `{}`",
+                pc,
+                program.location(pc).expect("in"),
+                program.get(pc).expect("in"))
+    }
+
+    /// The instruction at the program counter.
+    fn current(&self) -> Option<Instruction> {
+        self.interp.program().and_then(|p| p.get(self.interp.pc()))
+    }
+
+    /// The program counter of the given breakpoint.
+    fn resolve(&self, breakpoint: Breakpoint) -> Option<usize> {
+        let program = self.interp.program()?;
+        match breakpoint {
+            Breakpoint::Pc(pc) if pc < program.len() => Some(pc),
+            Breakpoint::Pc(_) => None,
+            Breakpoint::Position(p) => program.pc_at(p),
+        }
+    }
+}
+
+/// The touched cells within `radius` of the pointer, like `1:0 [2:3] 3:0`
+/// with the current cell in brackets. The cells past the touched ones are
+/// all `0`, and may go on without end, so they're left out.
+pub(crate) fn window<T: Tape>(tape: &T, radius: usize) -> String {
+    let ptr = tape.ptr();
+    let touched = tape.touched();
+    let start = ptr.saturating_sub(radius).max(touched.start);
+    let end = ptr.saturating_add(radius).saturating_add(1).min(touched.end);
+    let cells: Vec<String> = (start..end)
+        .filter_map(|i| tape.get(i).map(|v| {
+            if i == ptr { format!("[{}:{}]", i, v) } else { format!("{}:{}", i, v) }
+        }))
+        .collect();
+    cells.join(" ")
+}
+
+/// Parsing debugger commands.
+mod command;
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Program;
+    use crate::tape::{InfiniteTape, VecTape};
+    use super::*;
+
+    fn session(source: &str, commands: &[&str]) -> String {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse(source).unwrap());
+        let mut debugger = Debugger::new(interp);
+        let mut out = Vec::new();
+        for command in commands {
+            debugger.execute(command.parse().unwrap(), &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step() {
+        assert_eq!(session("set up\n++\n>", &["step", "step", "step 2"]),
+                   "at 1 (2:2) `+`\n    set up\nat 2 (3:1) `>`\n    set up\nFinished after 3 cycles\n");
+        assert_eq!(session("+", &["step", "where"]), "Finished after 1 cycles\nat the end of the program\n");
+    }
+
+    #[test]
+    fn step_zero() {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse("++").unwrap());
+        let mut debugger = Debugger::new(interp);
+        let mut out = Vec::new();
+        debugger.execute(Command::Step(0), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "at 0 (1:1) `+`\n");
+    }
+
+    #[test]
+    fn next() {
+        assert_eq!(session("++[->+<]>", &["n", "n", "n"]),
+                   "at 1 (1:2) `+`\nat 2 (1:3) `[`\nat 8 (1:9) `>`\n");
+    }
+
+    #[test]
+    fn breakpoints() {
+        let out = session("+++[-\n]+", &["b 5", "b 2:2", "b 99", "b", "c", "c", "d 5", "c"]);
+        assert_eq!(out, "Breakpoint 5 at 5 (2:1) `]`\n\
+                         Breakpoint 2:2 at 6 (2:2) `+`\n\
+                         No instruction at 99\n\
+                         5\n2:2\n\
+                         Breakpoint, at 5 (2:1) `]`\n\
+                         Breakpoint, at 5 (2:1) `]`\n\
+                         Breakpoint, at 6 (2:2) `+`\n");
+    }
+
+    #[test]
+    fn tape() {
+        let out = session(">>+++", &["c", "t 1", "p", "p 0", "set 0 7", "p 0", "t 3", "set 0 256"]);
+        assert_eq!(out, "Finished after 5 cycles\n\
+                         1:0 [2:3]\n\
+                         cell 2 = 3\n\
+                         cell 0 = 0\n\
+                         cell 0 = 7\n\
+                         0:7 1:0 [2:3]\n\
+                         Value 256 doesn't fit in a cell\n");
+    }
+
+    #[test]
+    fn huge_window() {
+        let mut interp = Interpreter::<InfiniteTape>::default();
+        interp.load(Program::parse("+<+>>").unwrap());
+        let mut debugger = Debugger::new(interp);
+        let mut out = Vec::new();
+        for command in &["c", "t 10000000000"] {
+            debugger.execute(command.parse().unwrap(), &mut out).unwrap();
+        }
+        assert_eq!(String::from_utf8(out).unwrap(), "Finished after 5 cycles\n0:1 1:1 [2:0]\n");
+    }
+
+    #[test]
+    fn error() {
+        assert_eq!(session("\n<", &["c"]), "Error: Tape pointer underflowed\nat 0 (2:1) `<`\n");
+    }
+
+    #[test]
+    fn quit() {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse("+").unwrap());
+        let mut out = Vec::new();
+        assert!(!Debugger::new(interp).execute(Command::Quit, &mut out).unwrap());
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The behavior of the `Input` instruction once the reader has no more data.
+///
+/// Brainfuck leaves reading past the end of input undefined, and the
+/// implementations in the wild disagree. `fixtures/tests.b` probes for the
+/// three common conventions, each of which has a variant here. The default
+/// is `Unchanged`, which is the convention most programs expect.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Eof {
+    /// Leave the value at the pointer untouched.
+    #[default]
+    Unchanged,
+    /// Write `0` to the value at the pointer.
+    Zero,
+    /// Write a value with every bit set to the value at the pointer, this
+    /// is `255` for `u8` cells, or `-1` when read as signed.
+    AllOnes,
+    /// Stop execution with `Error::Eof`.
+    Error,
+}
+
+impl fmt::Display for Eof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Eof::Unchanged => write!(f, "unchanged"),
+            Eof::Zero      => write!(f, "zero"),
+            Eof::AllOnes   => write!(f, "ones"),
+            Eof::Error     => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for Eof {
+    type Err = String;
+
+    /// Parse an EOF mode from the same names it displays as. The common
+    /// aliases `0`, `-1` and `255` are also accepted.
+    fn from_str(s: &str) -> Result<Eof, String> {
+        match s {
+            "unchanged"           => Ok(Eof::Unchanged),
+            "zero" | "0"          => Ok(Eof::Zero),
+            "ones" | "-1" | "255" => Ok(Eof::AllOnes),
+            "error"               => Ok(Eof::Error),
+            _ => Err(format!("Unknown EOF mode `{}`", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default() {
+        assert_eq!(Eof::default(), Eof::Unchanged);
+    }
+
+    #[test]
+    fn round_trip() {
+        for eof in &[Eof::Unchanged, Eof::Zero, Eof::AllOnes, Eof::Error] {
+            assert_eq!(eof.to_string().parse::<Eof>(), Ok(*eof));
+        }
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!("-1".parse::<Eof>(), Ok(Eof::AllOnes));
+        assert_eq!("0".parse::<Eof>(), Ok(Eof::Zero));
+        assert!("bogus".parse::<Eof>().is_err());
+    }
+}
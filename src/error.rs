@@ -1,4 +1,5 @@
 use std::{fmt, io};
+use super::Instruction;
 use super::tape;
 use super::program;
 
@@ -15,6 +16,63 @@ pub enum Error {
     NoProgram,
     /// Interpreter cycle limit hit.
     CycleLimit,
+    /// Attempted to read past the end of the input, see `Eof::Error`.
+    Eof,
+    /// An error from executing an instruction, with where it happened.
+    Runtime(Box<RuntimeError>),
+}
+
+impl Error {
+    /// The error without where it happened, this is the error itself for
+    /// every error but `Error::Runtime`.
+    ///
+    /// ```
+    /// use brainfuck::{Error, Interpreter};
+    /// use brainfuck::program::Program;
+    /// use brainfuck::tape::{self, VecTape};
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// let e = interp.load(Program::parse("+<").unwrap()).run().unwrap_err();
+    /// assert!(matches!(e.cause(), Error::Tape(tape::Error::PtrUnderflow)));
+    /// assert_eq!(e.to_string(), "Tape pointer underflowed at 1 (1:2) `<`, \
+    ///                            after 1 cycles with the pointer at 0");
+    /// ```
+    pub fn cause(&self) -> &Error {
+        match *self {
+            Error::Runtime(ref e) => &e.error,
+            _ => self,
+        }
+    }
+}
+
+/// An error from executing an instruction, with the state of the
+/// interpreter when it happened, see `Error::Runtime`.
+#[derive(Debug)]
+pub struct RuntimeError {
+    /// The error itself.
+    pub error: Error,
+    /// The program counter of the failing instruction.
+    pub pc: usize,
+    /// The failing instruction.
+    pub instruction: Instruction,
+    /// The cycles executed before the failing instruction.
+    pub cycles: u64,
+    /// The location of the pointer.
+    pub ptr: usize,
+    /// The location of the failing instruction in the source, like
+    /// `Program::location`.
+    pub location: Option<String>,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.error, self.pc)?;
+        if let Some(ref location) = self.location {
+            write!(f, " ({})", location)?;
+        }
+        write!(f, " `{}`, after {} cycles with the pointer at {}",
+               self.instruction, self.cycles, self.ptr)
+    }
 }
 
 impl fmt::Display for Error {
@@ -23,8 +81,10 @@ impl fmt::Display for Error {
             Error::Io(ref e) => e.fmt(f),
             Error::Tape(ref e) => e.fmt(f),
             Error::Program(ref e) => e.fmt(f),
-            Error::NoProgram => write!(f, "{}", "No program loaded"),
-            Error::CycleLimit => write!(f, "{}", "Cycle limit hit"),
+            Error::NoProgram => write!(f, "No program loaded"),
+            Error::CycleLimit => write!(f, "Cycle limit hit"),
+            Error::Eof => write!(f, "Read past the end of input"),
+            Error::Runtime(ref e) => e.fmt(f),
         }
     }
 }
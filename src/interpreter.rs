@@ -1,7 +1,8 @@
-use std::io::{Read, Write};
-use crate::tape::Tape;
+use std::io::{ErrorKind, Read, Write};
+use crate::ir::{Ir, Op};
+use crate::tape::{Cell, Tape};
 use crate::program::Program;
-use super::{CYCLE_LIMIT, Error, Instruction};
+use super::{CYCLE_LIMIT, Eof, Error, Instruction, RuntimeError, Status};
 
 /// A brainfuck interpreter, with the needed state for execution.
 ///
@@ -12,14 +13,39 @@ use super::{CYCLE_LIMIT, Error, Instruction};
 /// `read_from`, and `write_to` methods. The interpreter is also in charge
 /// of managing the program counter, which is `0` by default.
 ///
+/// Instead of running the whole program with `run`, it can be executed one
+/// instruction at a time with `step`, or a number of cycles at a time with
+/// `run_for`, inspecting the state with `pc`, `cycles`, `program` and `tape`
+/// in between. Both return a `Status`, so many interpreters can take turns
+/// running.
+///
+/// What happens when the program reads past the end of the input is set
+/// with the `on_eof` method, see `Eof` for the available conventions. A
+/// missing reader is treated as an empty one.
+///
+/// Every executed instruction counts as one cycle. By default an interpreter
+/// stops with `Error::CycleLimit` after `CYCLE_LIMIT` cycles, which guards
+/// against programs that never halt. The budget can be changed or removed
+/// with the `cycle_limit` method, and the cycles used so far are reported by
+/// `cycles`.
+///
+/// Running an optimized [`Ir`][ir] of the program instead of the program
+/// itself is enabled with the `optimize` method. This only changes how
+/// fast `run` is, the output, tape, program counter and cycle count are the
+/// same either way, with one exception. When an operation fails part way
+/// through, the program counter is left at the first of the instructions
+/// it replaced, and none of its cycles are counted. For an operation
+/// replacing a loop, the other cells it changes may also differ from where
+/// the program would have stopped.
+///
 /// Each interpreter stores a tape for the execution of the program. The
-/// current tape uses a dynamically allocated array of `TAPE_LENGTH` elements.
+/// tape is made with its `Default`, unless one is given with `use_tape`.
 ///
 /// Other fields used for instrumentation may also be stored in the
 /// interpreter.
 ///
 /// [top-doc]: index.html
-#[derive(Default)]
+/// [ir]: ir/struct.Ir.html
 pub struct Interpreter<'a, T: Tape> {
     program: Option<Program>,
     reader: Option<&'a mut dyn Read>,
@@ -27,6 +53,27 @@ pub struct Interpreter<'a, T: Tape> {
     tape: Box<T>,
     pc: usize,
     cycles: u64,
+    cycle_limit: Option<u64>,
+    eof: Eof,
+    optimize: bool,
+    ir: Option<Ir>,
+}
+
+impl<'a, T: Tape + Default> Default for Interpreter<'a, T> {
+    fn default() -> Self {
+        Interpreter {
+            program: None,
+            reader: None,
+            writer: None,
+            tape: Box::default(),
+            pc: 0,
+            cycles: 0,
+            cycle_limit: Some(CYCLE_LIMIT),
+            eof: Eof::default(),
+            optimize: false,
+            ir: None,
+        }
+    }
 }
 
 impl<'a, T: Tape + Default> Interpreter<'a, T> {
@@ -40,10 +87,25 @@ impl<'a, T: Tape + Default> Interpreter<'a, T> {
         interp
     }
 
-    /// Load a program for the interpreter to run.
+    /// Load a program for the interpreter to run, from its first
+    /// instruction. The tape and the count of cycles are kept, so programs
+    /// can be run one after another on the same tape.
+    ///
+    /// ```
+    /// use brainfuck::Interpreter;
+    /// use brainfuck::program::Program;
+    /// use brainfuck::tape::{Tape, VecTape};
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// interp.load(Program::parse("++>+").unwrap()).run().unwrap();
+    /// interp.load(Program::parse("<+").unwrap()).run().unwrap();
+    /// assert_eq!(interp.tape().get(0), Some(3));
+    /// assert_eq!(interp.cycles(), 6);
+    /// ```
     pub fn load(&mut self, program: Program) -> &mut Self {
         self.pc = 0;
         self.program = Some(program);
+        self.ir = None;
         self
     }
 
@@ -59,32 +121,146 @@ impl<'a, T: Tape + Default> Interpreter<'a, T> {
         self
     }
 
+    /// Use the given tape in place of the current one, for tapes made with
+    /// other settings than their defaults.
+    pub fn use_tape(&mut self, tape: T) -> &mut Self {
+        *self.tape = tape;
+        self
+    }
+
+    /// Use the given convention for the `Input` instruction when there is
+    /// no more input to read.
+    pub fn on_eof(&mut self, eof: Eof) -> &mut Self {
+        self.eof = eof;
+        self
+    }
+
+    /// Stop with `Error::CycleLimit` once the given number of cycles have
+    /// been executed, or never when `None`. A program which finishes in
+    /// exactly the limit isn't stopped. The count of cycles already
+    /// used is kept, so raising the limit allows a stopped program to
+    /// continue running.
+    pub fn cycle_limit(&mut self, limit: Option<u64>) -> &mut Self {
+        self.cycle_limit = limit;
+        self
+    }
+
+    /// The number of cycles executed so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Run the optimized IR of the program instead of the program itself
+    /// when `run` is called.
+    pub fn optimize(&mut self, optimize: bool) -> &mut Self {
+        self.optimize = optimize;
+        self
+    }
+
     /// Run the interpreter.
     pub fn run(&mut self) -> Result<(), Error> {
-        while let Some(r) = self.step()? {
-            if let Err(e) = r {
-                return Err(e)
-            }
-        };
-        Ok(())
+        if self.optimize {
+            self.run_ir()
+        } else {
+            self.run_steps()
+        }
     }
 
     /// Run the interpreter with a callback hook.
     pub fn run_with_callback<F>(&mut self, mut hook: F) -> Result<(), Error>
     where F: FnMut(&mut Self, &Instruction) {
-        while let Some(r) = self.step()? {
-            match r {
-                Ok(i) => hook(self, &i),
-                Err(e) => return Err(e),
-            }
+        while let Some(i) = self.step_instruction()? {
+            hook(self, &i);
         };
         Ok(())
     }
 
-    fn step(&mut self) -> Result<Option<Result<Instruction, Error>>, Error> {
-        if self.cycles >= CYCLE_LIMIT {
-            return Ok(Some(Err(Error::CycleLimit)))
+    /// Execute the instruction at the program counter. Stepping never uses
+    /// the optimized IR.
+    ///
+    /// ```
+    /// use brainfuck::{Interpreter, Status};
+    /// use brainfuck::program::Program;
+    /// use brainfuck::tape::VecTape;
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// interp.load(Program::parse(">+").unwrap());
+    /// assert!(interp.step().is_running());
+    /// assert!(interp.step().is_halted());
+    /// assert_eq!(interp.pc(), 2);
+    /// ```
+    pub fn step(&mut self) -> Status {
+        match self.step_instruction() {
+            Ok(_) if self.is_finished() => Status::Halted,
+            Ok(_) => Status::Running,
+            Err(e) => Status::Error(e),
+        }
+    }
+
+    /// Run the program for at most the given number of cycles, stopping
+    /// early if it finishes, or fails. The cycle limit still applies, and is
+    /// reported with `Error::CycleLimit` as usual.
+    ///
+    /// ```
+    /// use brainfuck::{Interpreter, Status};
+    /// use brainfuck::program::Program;
+    /// use brainfuck::tape::VecTape;
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// interp.load(Program::parse("+++++").unwrap());
+    /// assert!(interp.run_for(3).is_running());
+    /// assert_eq!(interp.cycles(), 3);
+    /// assert!(interp.run_for(3).is_halted());
+    /// assert_eq!(interp.cycles(), 5);
+    /// ```
+    pub fn run_for(&mut self, cycles: u64) -> Status {
+        let limit = self.cycle_limit;
+        let pause = self.cycles.saturating_add(cycles);
+        // Pausing is done with the cycle limit, unless the real limit comes
+        // first.
+        let paused = limit.is_none_or(|l| pause < l);
+        if paused {
+            self.cycle_limit = Some(pause);
+        }
+        let result = self.run();
+        self.cycle_limit = limit;
+        match result {
+            Ok(()) => Status::Halted,
+            Err(Error::CycleLimit) if paused && !self.is_finished() => Status::Running,
+            Err(Error::CycleLimit) if paused => Status::Halted,
+            Err(e) => Status::Error(e),
         }
+    }
+
+    /// The program counter, the index of the next instruction to execute.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The loaded program, if any.
+    pub fn program(&self) -> Option<&Program> {
+        self.program.as_ref()
+    }
+
+    /// The tape the program runs on.
+    pub fn tape(&self) -> &T {
+        &self.tape
+    }
+
+    /// The tape the program runs on, for changing cells between steps.
+    pub fn tape_mut(&mut self) -> &mut T {
+        &mut self.tape
+    }
+
+    fn run_steps(&mut self) -> Result<(), Error> {
+        while self.step_instruction()?.is_some() {}
+        Ok(())
+    }
+
+    /// Execute the instruction at the program counter, returning it, or
+    /// `None` when the program has finished.
+    fn step_instruction(&mut self) -> Result<Option<Instruction>, Error> {
+        // A program which finishes in exactly the limit isn't stopped.
         let instruction = match self.program {
             Some(ref p) => match p.get(self.pc) {
                 Some(i) => i,
@@ -92,13 +268,40 @@ impl<'a, T: Tape + Default> Interpreter<'a, T> {
             },
             None => return Err(Error::NoProgram),
         };
-        match self.execute(instruction) {
-            Ok(_) => {
-                self.cycles += 1;
-                Ok(Some(Ok(instruction)))
-            },
-            Err(e) => Ok(Some(Err(e))),
+        if let Some(limit) = self.cycle_limit {
+            if self.cycles >= limit {
+                return Err(Error::CycleLimit)
+            }
         }
+        self.execute(instruction).map_err(|e| self.locate(e))?;
+        self.cycles += 1;
+        Ok(Some(instruction))
+    }
+
+    /// Add where the program is to an error from executing the instruction
+    /// at the program counter.
+    fn locate(&self, error: Error) -> Error {
+        let program = match self.program {
+            Some(ref p) => p,
+            None => return error,
+        };
+        match (error, program.get(self.pc)) {
+            (error @ Error::Runtime(_), _) => error,
+            (error, Some(instruction)) => Error::Runtime(Box::new(RuntimeError {
+                error,
+                pc: self.pc,
+                instruction,
+                cycles: self.cycles,
+                ptr: self.tape.ptr(),
+                location: program.location(self.pc),
+            })),
+            (error, None) => error,
+        }
+    }
+
+    /// Returns true when there's a program, and nothing left of it to run.
+    fn is_finished(&self) -> bool {
+        self.program.as_ref().is_some_and(|p| self.pc >= p.len())
     }
 
     fn execute(&mut self, instruction: Instruction) -> Result<Instruction, Error> {
@@ -116,39 +319,233 @@ impl<'a, T: Tape + Default> Interpreter<'a, T> {
                 self.tape.dec_val()?;
             },
             Instruction::Output => {
-                if let Some(ref mut w) = self.writer {
-                    w.write(&[**self.tape])?;
-                }
+                self.output()?;
             },
             Instruction::Input => {
-                if let Some(ref mut r) = self.reader {
-                    if let Some(b) = r.bytes().next() {
-                        **self.tape = b?;
-                    }
-                }
+                self.input()?;
             },
             Instruction::SkipForward(iptr) => {
-                if **self.tape == 0 {
+                if self.tape.is_zero() {
                     self.pc = iptr;
                 }
             },
             Instruction::SkipBackward(iptr) => {
-                if **self.tape != 0 {
+                if !self.tape.is_zero() {
                     self.pc = iptr;
                 }
             },
         };
-        self.pc = self.pc + 1;
+        self.pc += 1;
         Ok(instruction)
     }
+
+    fn run_ir(&mut self) -> Result<(), Error> {
+        if self.ir.is_none() {
+            let program = self.program.as_ref().ok_or(Error::NoProgram)?;
+            self.ir = Some(Ir::new(program));
+        }
+        // The IR is put back once done, so it's only built once per program.
+        let ir = self.ir.take().expect("built");
+        let result = self.run_ops(&ir);
+        self.ir = Some(ir);
+        result
+    }
+
+    fn run_ops(&mut self, ir: &Ir) -> Result<(), Error> {
+        let end = self.program.as_ref().map_or(0, Program::len);
+        // When resuming part way through an operation, the rest of it is
+        // stepped through one instruction at a time.
+        let mut index = loop {
+            match ir.locate(self.pc) {
+                Some((index, 0)) => break index,
+                Some(_) => {
+                    self.step_instruction()?;
+                },
+                None => return Ok(()),
+            }
+        };
+        while index < ir.len() {
+            self.pc = ir.pc(index).expect("in");
+            let budget = match self.cycle_limit {
+                Some(limit) => limit.saturating_sub(self.cycles),
+                None => u64::MAX,
+            };
+            match self.execute_op(ir, index, budget).map_err(|e| self.locate(e))? {
+                Some((next, cycles)) => {
+                    self.cycles = self.cycles.saturating_add(cycles);
+                    index = next;
+                },
+                // Not enough cycles left for the whole operation, so step
+                // through the program to stop exactly where running it
+                // would.
+                None => return self.run_steps(),
+            }
+        }
+        self.pc = end;
+        Ok(())
+    }
+
+    /// Execute the operation at the given index, unless it would use more
+    /// than `budget` cycles. Returns the index of the next operation and the
+    /// cycles used, or `None` when nothing was done.
+    fn execute_op(&mut self, ir: &Ir, index: usize, budget: u64) -> Result<Option<(usize, u64)>, Error> {
+        let op = ir.get(index).expect("in");
+        let width = ir.replaces(index).expect("in").len() as u64;
+        let cycles = match op {
+            Op::SetZero | Op::MulAdd(..) | Op::ScanRight(_) | Op::ScanLeft(_)
+                if width > 0 && !self.undoes_moves(ir, index) => return self.step_loop(ir, index),
+            Op::ScanRight(stride) => {
+                return Ok(self.scan(stride as isize, width, budget)?.map(|c| (index + 1, c)))
+            },
+            Op::ScanLeft(stride) => {
+                return Ok(self.scan(-(stride as isize), width, budget)?.map(|c| (index + 1, c)))
+            },
+            // Entering the loop, and then a pass through the rest of it for
+            // each iteration. Operations which replace no instructions are
+            // part of the loop before them, and already counted.
+            Op::SetZero | Op::MulAdd(..) if width > 0 => match self.value() {
+                Some(v) => v.saturating_mul(width - 1).saturating_add(1),
+                None => return self.step_loop(ir, index),
+            },
+            _ => width,
+        };
+        if cycles > budget {
+            return Ok(None)
+        }
+        let mut next = index + 1;
+        match op {
+            Op::AddVal(n) => self.tape.add_val(n)?,
+            Op::MovePtr(n) => self.tape.move_ptr(n)?,
+            Op::Output => self.output()?,
+            Op::Input => self.input()?,
+            Op::JumpForward(target) => if self.tape.is_zero() {
+                next = target + 1;
+            },
+            Op::JumpBackward(target) => if !self.tape.is_zero() {
+                next = target + 1;
+            },
+            Op::SetZero => **self.tape = T::Cell::default(),
+            Op::MulAdd(offset, factor) => {
+                let value = self.value().expect("counted down");
+                if value != 0 {
+                    self.tape.move_ptr(offset)?;
+                    // Wide cells can need more than an `isize`, the same
+                    // sum is added in parts.
+                    let mut sum = value as i128 * factor as i128;
+                    while sum != 0 {
+                        let n = sum.clamp(isize::MIN as i128, isize::MAX as i128);
+                        self.tape.add_val(n as isize)?;
+                        sum -= n;
+                    }
+                    self.tape.move_ptr(-offset)?;
+                }
+            },
+            Op::ScanRight(_) | Op::ScanLeft(_) => unreachable!(),
+        }
+        Ok(Some((next, cycles)))
+    }
+
+    /// Move the pointer by `stride` until the value at the pointer is `0`,
+    /// for a loop of `width` instructions, unless it would use more than
+    /// `budget` cycles. Returns the cycles used, or `None` when the pointer
+    /// was left where it started.
+    fn scan(&mut self, stride: isize, width: u64, budget: u64) -> Result<Option<u64>, Error> {
+        let mut cycles = 1;
+        let mut moves = 0;
+        if cycles > budget {
+            return Ok(None)
+        }
+        while !self.tape.is_zero() {
+            cycles += width - 1;
+            if cycles > budget {
+                self.tape.move_ptr(-stride * moves)?;
+                return Ok(None)
+            }
+            self.tape.move_ptr(stride)?;
+            moves += 1;
+        }
+        Ok(Some(cycles))
+    }
+
+    /// Returns true if the tape can undo each move of the pointer made by
+    /// the operations replacing the loop at the given index. When it can't,
+    /// like at the ends of a tape which clamps the pointer, the loop is
+    /// stepped through instead.
+    fn undoes_moves(&self, ir: &Ir, index: usize) -> bool {
+        (index..ir.len())
+            .take_while(|&i| i == index || ir.replaces(i).expect("in").is_empty())
+            .all(|i| match ir.get(i).expect("in") {
+                Op::MulAdd(offset, _) => self.tape.undoes_move(offset),
+                Op::ScanRight(stride) => self.tape.undoes_move(stride as isize),
+                Op::ScanLeft(stride) => self.tape.undoes_move(-(stride as isize)),
+                _ => true,
+            })
+    }
+
+    /// Step through the loop replaced by the operation at the given index,
+    /// returning the index of the operation after it. The cycles are
+    /// counted by the steps.
+    fn step_loop(&mut self, ir: &Ir, index: usize) -> Result<Option<(usize, u64)>, Error> {
+        let end = ir.replaces(index).expect("in").end;
+        while self.pc < end {
+            self.step_instruction()?;
+        }
+        let next = ir.locate(end).map_or(ir.len(), |(next, _)| next);
+        Ok(Some((next, 0)))
+    }
+
+    /// The value at the pointer, as a count of loop iterations for a loop
+    /// decrementing it by 1. This is `None` for negative values, which
+    /// only reach `0` by wrapping around, if the tape allows it, and values
+    /// too large to count.
+    fn value(&self) -> Option<u64> {
+        self.tape.to_u64()
+    }
+
+    fn output(&mut self) -> Result<(), Error> {
+        if let Some(ref mut w) = self.writer {
+            w.write_all(&[self.tape.to_byte()])?;
+        }
+        Ok(())
+    }
+
+    fn input(&mut self) -> Result<(), Error> {
+        match self.read_byte()? {
+            Some(b) => **self.tape = T::Cell::from_byte(b),
+            None => match self.eof {
+                Eof::Unchanged => {},
+                Eof::Zero => **self.tape = T::Cell::default(),
+                Eof::AllOnes => **self.tape = T::Cell::all_ones(),
+                Eof::Error => return Err(Error::Eof),
+            },
+        }
+        Ok(())
+    }
+
+    /// Read a single byte from the reader, returning `None` at the end of
+    /// the input.
+    fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+        let reader = match self.reader {
+            Some(ref mut r) => r,
+            None => return Ok(None),
+        };
+        let mut buf = [0];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {},
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io;
-    use Instruction;
     use crate::program::Program;
-    use crate::tape::VecTape;
+    use crate::tape::{ModArrayTape, PointerPolicy, PolicyTape, ValuePolicy, VecTape};
     use super::*;
 
     // Public functions.
@@ -183,7 +580,7 @@ mod tests {
         interp.load(program.unwrap());
         let mut count = 0;
         assert!(interp.run_with_callback(|_, _| {
-            count = count + 1
+            count += 1
         }).is_ok());
         assert_eq!(count, 5);
     }
@@ -195,11 +592,9 @@ mod tests {
         let program = Program::parse("++>+.");
         let mut interp = Interpreter::<VecTape>::default();
         interp.load(program.unwrap());
-        let result = interp.step();
-        assert!(result.is_ok());
-        let run = result.unwrap();
-        assert!(run.is_some());
-        assert!(run.unwrap().is_ok());
+        assert!(interp.step().is_running());
+        assert_eq!(interp.pc(), 1);
+        assert_eq!(interp.cycles(), 1);
     }
 
     #[test]
@@ -215,7 +610,99 @@ mod tests {
         let program = Program::parse(">");
         let mut interp = Interpreter::<VecTape>::default();
         interp.load(program.unwrap());
-        interp.step().unwrap().unwrap().unwrap();
+        assert!(interp.step().is_halted());
+        assert_eq!(interp.tape().ptr(), 1);
+        assert!(interp.step().is_halted());
+        assert_eq!(interp.pc(), 1);
+        assert_eq!(interp.cycles(), 1);
+    }
+
+    #[test]
+    fn step_errors() {
+        let mut interp = Interpreter::<VecTape>::default();
+        assert!(matches!(interp.step(), Status::Error(Error::NoProgram)));
+        interp.load(Program::parse("<").unwrap());
+        match interp.step() {
+            Status::Error(Error::Runtime(e)) => {
+                assert!(matches!(e.error, Error::Tape(_)));
+                assert_eq!((e.pc, e.instruction, e.cycles, e.ptr), (0, Instruction::DecPtr, 0, 0));
+                assert_eq!(e.location.as_deref(), Some("1:1"));
+            },
+            s => panic!("expected a runtime error, got {:?}", s),
+        }
+        assert_eq!(interp.pc(), 0);
+        interp.load(Program::parse("+").unwrap()).cycle_limit(Some(0));
+        assert!(matches!(interp.step(), Status::Error(Error::CycleLimit)));
+    }
+
+    #[test]
+    fn runtime_error_context() {
+        for &optimize in &[false, true] {
+            let mut program = Program::parse("+\n>--").unwrap();
+            program.set_name("test.b");
+            let mut interp = Interpreter::<VecTape>::default();
+            interp.load(program).optimize(optimize);
+            match interp.run() {
+                Err(Error::Runtime(e)) => {
+                    assert!(matches!(e.error, Error::Tape(_)));
+                    assert_eq!((e.pc, e.instruction, e.ptr), (2, Instruction::DecVal, 1));
+                    assert_eq!(e.location.as_deref(), Some("test.b:2:2"));
+                },
+                r => panic!("expected a runtime error, got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn run_for() {
+        let source = "++[>+++[>+<-]<-]>>.";
+        for &optimize in &[false, true] {
+            let mut writer = Vec::<u8>::new();
+            let mut interp = Interpreter::<VecTape>::default();
+            interp.load(Program::parse(source).unwrap())
+                .write_to(&mut writer)
+                .optimize(optimize);
+            let mut turns = 0;
+            while interp.run_for(5).is_running() {
+                turns += 1;
+                assert_eq!(interp.cycles(), turns * 5);
+            }
+            assert_eq!(interp.cycles(), 52);
+            assert_eq!(turns, 10);
+            drop(interp);
+            assert_eq!(writer, [6]);
+        }
+    }
+
+    #[test]
+    fn run_for_exact() {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse("+++").unwrap());
+        assert!(interp.run_for(3).is_halted());
+        assert!(interp.run_for(3).is_halted());
+        assert_eq!(interp.cycles(), 3);
+    }
+
+    #[test]
+    fn run_for_cycle_limit() {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse("+[]").unwrap()).cycle_limit(Some(10));
+        assert!(interp.run_for(4).is_running());
+        assert!(matches!(interp.run_for(100), Status::Error(Error::CycleLimit)));
+        assert_eq!(interp.cycles(), 10);
+        interp.cycle_limit(None);
+        assert!(interp.run_for(100).is_running());
+        assert_eq!(interp.cycles(), 110);
+    }
+
+    #[test]
+    fn tape_mut() {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse(".").unwrap());
+        interp.tape_mut().set(0, 65).unwrap();
+        let mut writer = Vec::<u8>::new();
+        interp.write_to(&mut writer).run().unwrap();
+        assert_eq!(writer, b"A");
     }
 
     #[test]
@@ -232,4 +719,274 @@ mod tests {
         }
         assert_eq!(writer, [1]);
     }
+
+    #[test]
+    fn cycles() {
+        let program = Program::parse("++>+.");
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(program.unwrap());
+        assert_eq!(interp.cycles(), 0);
+        interp.run().unwrap();
+        assert_eq!(interp.cycles(), 5);
+    }
+
+    #[test]
+    fn cycle_limit() {
+        let program = Program::parse("++>+.");
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(program.unwrap());
+        interp.cycle_limit(Some(3));
+        assert!(matches!(interp.run(), Err(Error::CycleLimit)));
+        assert_eq!(interp.cycles(), 3);
+        // Raising the limit lets the program pick up where it stopped.
+        interp.cycle_limit(Some(10));
+        interp.run().unwrap();
+        assert_eq!(interp.cycles(), 5);
+    }
+
+    #[test]
+    fn exact_cycle_limit() {
+        for &optimize in &[false, true] {
+            let mut interp = Interpreter::<VecTape>::default();
+            interp.load(Program::parse("+++[-]").unwrap()).cycle_limit(Some(10)).optimize(optimize);
+            interp.run().unwrap();
+            assert_eq!(interp.cycles(), 10);
+            interp.load(Program::parse("").unwrap()).cycle_limit(Some(0));
+            interp.run().unwrap();
+            interp.load(Program::parse("+").unwrap()).cycle_limit(Some(10));
+            assert!(matches!(interp.run(), Err(Error::CycleLimit)), "{}", optimize);
+        }
+    }
+
+    #[test]
+    fn no_cycle_limit() {
+        let program = Program::parse("++>+.");
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(program.unwrap());
+        interp.cycle_limit(Some(2)).cycle_limit(None);
+        interp.run().unwrap();
+        assert_eq!(interp.cycles(), 5);
+    }
+
+    /// The result, output, pc and cycles of a run.
+    type Outcome = (Result<(), String>, Vec<u8>, usize, u64);
+
+    fn run_both(source: &str, limit: Option<u64>) -> [Outcome; 2] {
+        run_both_on::<VecTape>(source, limit)
+    }
+
+    fn run_both_on<T: Tape + Default>(source: &str, limit: Option<u64>) -> [Outcome; 2] {
+        let run = |optimize| {
+            let mut writer = Vec::<u8>::new();
+            let (result, pc, cycles) = {
+                let mut interp = Interpreter::<T>::default();
+                interp.write_to(&mut writer);
+                interp.load(Program::parse(source).unwrap());
+                interp.optimize(optimize).cycle_limit(limit);
+                let result = interp.run().map_err(|e| e.cause().to_string());
+                (result, interp.pc, interp.cycles)
+            };
+            (result, writer, pc, cycles)
+        };
+        [run(false), run(true)]
+    }
+
+    #[test]
+    fn optimize() {
+        let source = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.";
+        let [plain, optimized] = run_both(source, None);
+        assert!(plain.0.is_ok());
+        assert_eq!(plain, optimized);
+    }
+
+    #[test]
+    fn optimize_cycle_limit() {
+        // Stop in the middle of the run of `+`, and in the loop.
+        for &limit in &[5, 30] {
+            let [plain, optimized] = run_both("++++++++[>++++<-]>.", Some(limit));
+            assert!(plain.0.is_err());
+            assert_eq!(plain, optimized);
+        }
+    }
+
+    #[test]
+    fn optimize_idioms() {
+        let sources = ["+++++[>+++<-]>[-]+++[>++>+<<-]>.>.",
+                       ">+>+>+>+<<<[>]<+.[<]>.",
+                       "+++++++[>+>++<<-]>>[<]>[>>+<<-]>>."];
+        for source in &sources {
+            let [plain, optimized] = run_both(source, None);
+            assert!(plain.0.is_ok());
+            assert_eq!(plain, optimized, "{}", source);
+            // Stopping at every cycle along the way.
+            for limit in 0..plain.3 {
+                let [plain, optimized] = run_both(source, Some(limit));
+                assert!(plain.0.is_err());
+                assert_eq!(plain, optimized, "{} limit {}", source, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn optimize_clamped_pointer() {
+        // On a single cell with a clamped pointer `>+<` adds to the counter,
+        // so the first loop never ends, and `[<]` never leaves the cell.
+        let run = |source, optimize| {
+            let mut tape: PolicyTape = PolicyTape::with_length(1);
+            tape.values(ValuePolicy::Wrap).pointer(PointerPolicy::Clamp);
+            let mut interp = Interpreter::<PolicyTape>::default();
+            interp.use_tape(tape).load(Program::parse(source).unwrap());
+            interp.optimize(optimize).cycle_limit(Some(100));
+            let result = interp.run().map_err(|e| e.cause().to_string());
+            (result, **interp.tape(), interp.pc, interp.cycles)
+        };
+        for source in &["+[->+<]", "+[<]", "+[>>]"] {
+            let plain = run(source, false);
+            assert_eq!(plain.0, Err("Cycle limit hit".into()));
+            assert_eq!(plain, run(source, true), "{}", source);
+        }
+    }
+
+    #[test]
+    fn optimize_wrapped_pointer() {
+        // A move all the way around the tape lands on the counter.
+        let source = "+[->>+<<]";
+        let run = |optimize| {
+            let mut interp = Interpreter::<ModArrayTape>::default();
+            interp.use_tape(ModArrayTape::with_length(2)).load(Program::parse(source).unwrap());
+            interp.optimize(optimize).cycle_limit(Some(100));
+            let result = interp.run().map_err(|e| e.cause().to_string());
+            (result, interp.tape().get(0), interp.pc, interp.cycles)
+        };
+        assert!(run(false).0.is_err());
+        assert_eq!(run(false), run(true));
+    }
+
+    #[test]
+    fn wide_cells() {
+        // Prints `1` when a cell can hold 256.
+        let source = "++++++++++++++++[>++++++++++++++++<-]>[>+++++++[<+++++++>-]<.[-]]";
+        let [plain, optimized] = run_both_on::<ModArrayTape>(source, None);
+        assert_eq!(plain.1, b"");
+        assert_eq!(plain, optimized);
+        let [plain, optimized] = run_both_on::<ModArrayTape<u16>>(source, None);
+        assert_eq!(plain.1, b"1");
+        assert_eq!(plain, optimized);
+    }
+
+    #[test]
+    fn wide_io() {
+        let mut reader: &[u8] = &[200];
+        let mut writer = Vec::<u8>::new();
+        {
+            let program = Program::parse(",[->++<]>.,.").unwrap();
+            let mut interp = Interpreter::<VecTape<u32>>::new(program, &mut reader, &mut writer);
+            interp.on_eof(Eof::AllOnes).optimize(true).run().unwrap();
+            assert_eq!(**interp.tape(), u32::MAX);
+            assert_eq!(interp.tape().get(0), Some(0));
+        }
+        // The lowest 8 bits of 400, and then of all ones.
+        assert_eq!(writer, [144, 255]);
+    }
+
+    #[test]
+    fn optimize_negative_counter() {
+        // Counting down from -1 wraps around on a wrapping tape, and fails
+        // on any other.
+        let source = "-[>+<-]>.";
+        let [plain, optimized] = run_both_on::<ModArrayTape<i8>>(source, None);
+        assert_eq!(plain.1, [255]);
+        assert_eq!(plain, optimized);
+        let [plain, optimized] = run_both_on::<VecTape<i8>>(source, None);
+        assert_eq!(plain.0, Err("Tape value overflowed".into()));
+        assert_eq!(plain, optimized);
+    }
+
+    #[test]
+    fn optimize_huge_counter() {
+        let mut writer = Vec::<u8>::new();
+        {
+            let program = Program::parse("-[>++<-]>.").unwrap();
+            let mut interp = Interpreter::<ModArrayTape<u64>>::default();
+            interp.load(program).write_to(&mut writer).optimize(true).cycle_limit(None);
+            interp.run().unwrap();
+            assert_eq!(**interp.tape(), u64::MAX - 1);
+        }
+        assert_eq!(writer, [254]);
+    }
+
+    #[test]
+    fn optimize_resume() {
+        let program = Program::parse("++++++++.");
+        let mut writer = Vec::<u8>::new();
+        {
+            let mut interp = Interpreter::<VecTape>::default();
+            interp.write_to(&mut writer);
+            interp.load(program.unwrap());
+            interp.optimize(true).cycle_limit(Some(3));
+            assert!(interp.run().is_err());
+            assert_eq!(interp.pc, 3);
+            interp.cycle_limit(None);
+            interp.run().unwrap();
+            assert_eq!(interp.cycles, 9);
+        }
+        assert_eq!(writer, [8]);
+    }
+
+    #[test]
+    fn optimize_error() {
+        let [plain, optimized] = run_both("+--", None);
+        assert!(plain.0.is_err());
+        assert_eq!(plain.0, optimized.0);
+        assert_eq!((plain.2, plain.3), (2, 2));
+        // The run of `-` is not counted, leaving the pc on its first `-`.
+        assert_eq!((optimized.2, optimized.3), (1, 1));
+    }
+
+    fn eof_output(eof: Eof) -> Result<Vec<u8>, Error> {
+        let mut reader = "a".as_bytes();
+        let mut writer = Vec::<u8>::new();
+        let program = Program::parse("+,.,.");
+        {
+            let mut interp = Interpreter::<VecTape>::default();
+            interp.read_from(&mut reader);
+            interp.write_to(&mut writer);
+            interp.on_eof(eof);
+            interp.load(program.unwrap());
+            interp.run()?;
+        }
+        Ok(writer)
+    }
+
+    #[test]
+    fn eof_unchanged() {
+        assert_eq!(eof_output(Eof::Unchanged).unwrap(), b"aa");
+    }
+
+    #[test]
+    fn eof_zero() {
+        assert_eq!(eof_output(Eof::Zero).unwrap(), b"a\0");
+    }
+
+    #[test]
+    fn eof_all_ones() {
+        assert_eq!(eof_output(Eof::AllOnes).unwrap(), b"a\xff");
+    }
+
+    #[test]
+    fn eof_error() {
+        match eof_output(Eof::Error) {
+            Err(ref e) if matches!(e.cause(), Error::Eof) => {},
+            r => panic!("expected Error::Eof, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn eof_without_reader() {
+        let program = Program::parse(",");
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.on_eof(Eof::Error);
+        interp.load(program.unwrap());
+        assert!(matches!(interp.run().unwrap_err().cause(), Error::Eof));
+    }
 }
@@ -0,0 +1,152 @@
+use super::{Ir, Op};
+
+/// Replace the loops of a folded IR which have a single operation
+/// equivalent.
+///
+/// - `[-]` becomes `SetZero`.
+/// - A loop made only of value and pointer changes, which leaves the pointer
+///   where it started and decrements the value there by exactly 1 per
+///   iteration, becomes a `MulAdd` for every other cell it changes followed
+///   by a `SetZero`. For example `[->+>--<<]` becomes
+///   `MulAdd(1, 1) MulAdd(2, -2) SetZero`.
+/// - A loop of a single pointer move becomes a `ScanRight` or `ScanLeft`.
+///
+/// Loops which change a cell more than once per iteration, or move the
+/// pointer somewhere without changing the cell there, are left alone. On a
+/// tape which errors, their plain execution can fail where the replacement
+/// wouldn't.
+///
+/// On a tape of `length` cells whose pointer wraps around, a `MulAdd` with
+/// an offset of a multiple of the length would land back on the counter, so
+/// those loops are left alone too.
+pub(super) fn replace(ir: Ir, length: Option<usize>) -> Ir {
+    let wraps_onto_counter = |op: &Op| match (*op, length) {
+        (Op::MulAdd(offset, _), Some(length)) => offset % length as isize == 0,
+        _ => false,
+    };
+    let mut out = Ir::default();
+    let mut index = 0;
+    while index < ir.ops.len() {
+        if let Op::JumpForward(close) = ir.ops[index] {
+            let ops = idiom(&ir.ops[index + 1..close])
+                .filter(|ops| !ops.iter().any(wraps_onto_counter));
+            if let Some(ops) = ops {
+                let start = ir.ranges[index].start;
+                let end = ir.ranges[close].end;
+                for (i, op) in ops.into_iter().enumerate() {
+                    // The first operation stands for the whole loop.
+                    out.push(op, if i == 0 { start..end } else { start..start });
+                }
+                index = close + 1;
+                continue;
+            }
+        }
+        out.push(ir.ops[index], ir.ranges[index].clone());
+        index += 1;
+    }
+    out.link();
+    out
+}
+
+/// The operations equivalent to a loop with the given body, if any.
+fn idiom(body: &[Op]) -> Option<Vec<Op>> {
+    match *body {
+        [Op::MovePtr(n)] if n > 0 => return Some(vec![Op::ScanRight(n as usize)]),
+        [Op::MovePtr(n)] => return Some(vec![Op::ScanLeft(n.unsigned_abs())]),
+        _ => {},
+    }
+    // The change to each cell, by offset from the pointer at the start of
+    // the loop.
+    let mut changes: Vec<(isize, isize)> = Vec::new();
+    let mut ptr = 0;
+    let mut moved = false;
+    for op in body {
+        match *op {
+            Op::MovePtr(n) if !moved => {
+                ptr += n;
+                moved = true;
+            },
+            Op::AddVal(n) if !changes.iter().any(|c| c.0 == ptr) => {
+                changes.push((ptr, n));
+                moved = false;
+            },
+            _ => return None,
+        }
+    }
+    if ptr != 0 {
+        return None
+    }
+    let counter = changes.iter().position(|&c| c == (0, -1))?;
+    changes.remove(counter);
+    let mut ops: Vec<Op> = changes.into_iter()
+        .map(|(offset, factor)| Op::MulAdd(offset, factor))
+        .collect();
+    ops.push(Op::SetZero);
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Program;
+    use super::*;
+
+    fn ops(source: &str) -> Vec<Op> {
+        Ir::new(&Program::parse(source).unwrap()).ops
+    }
+
+    #[test]
+    fn set_zero() {
+        assert_eq!(ops("+[-]."), vec![Op::AddVal(1), Op::SetZero, Op::Output]);
+        assert_eq!(ops("[+]").len(), 3);
+    }
+
+    #[test]
+    fn mul_add() {
+        assert_eq!(ops("[->+>--<<]"),
+                   vec![Op::MulAdd(1, 1), Op::MulAdd(2, -2), Op::SetZero]);
+        assert_eq!(ops("[<+++>-]"), vec![Op::MulAdd(-1, 3), Op::SetZero]);
+    }
+
+    #[test]
+    fn not_mul_add() {
+        // Changing a cell twice.
+        assert_eq!(ops("[->+-<]").len(), 7);
+        // Counting down by 2.
+        assert_eq!(ops("[-->+<]").len(), 6);
+        // Moving without changing.
+        assert_eq!(ops("[->><<]").len(), 5);
+        // Not returning to the start.
+        assert_eq!(ops("[->+]").len(), 5);
+        // Doing IO.
+        assert_eq!(ops("[->.<]").len(), 6);
+    }
+
+    #[test]
+    fn scan() {
+        assert_eq!(ops("[>]"), vec![Op::ScanRight(1)]);
+        assert_eq!(ops("[<<<]"), vec![Op::ScanLeft(3)]);
+    }
+
+    #[test]
+    fn wrapping() {
+        let ops = |source, length| Ir::wrapping(&Program::parse(source).unwrap(), length).ops;
+        assert_eq!(ops("[->>+<<]", 3), vec![Op::MulAdd(2, 1), Op::SetZero]);
+        assert_eq!(ops("[->>+<<]", 2).len(), 6);
+        assert_eq!(ops("[>>]", 2), vec![Op::ScanRight(2)]);
+    }
+
+    #[test]
+    fn nested() {
+        let ir = Ir::new(&Program::parse("+[>[-]<-]").unwrap());
+        assert_eq!(ir.ops, vec![Op::AddVal(1), Op::JumpForward(6),
+                                Op::MovePtr(1), Op::SetZero, Op::MovePtr(-1),
+                                Op::AddVal(-1), Op::JumpBackward(1)]);
+        assert_eq!(ir.ranges[3], 3..6);
+    }
+
+    #[test]
+    fn ranges() {
+        let ir = Ir::new(&Program::parse("+[->+<]").unwrap());
+        assert_eq!(ir.ranges, vec![0..1, 1..7, 1..1]);
+    }
+}
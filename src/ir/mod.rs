@@ -0,0 +1,271 @@
+use std::fmt;
+use std::ops::Range;
+use crate::Instruction;
+use crate::program::Program;
+
+/// An operation in the optimized representation of a program.
+///
+/// Each operation stands for one or more instructions of the program it was
+/// built from. Runs of the same value or pointer instruction are folded into
+/// a single `AddVal` or `MovePtr`, and a few common loops are replaced by
+/// the operations at the end of this list, see the `idiom` pass for which.
+/// The rest map one to one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// Add to the value at the pointer, negative amounts subtract.
+    AddVal(isize),
+    /// Move the pointer up the tape, negative amounts move it down.
+    MovePtr(isize),
+    /// Same as `Instruction::Output`.
+    Output,
+    /// Same as `Instruction::Input`.
+    Input,
+    /// Jump to the operation with the given index if the value at the pointer
+    /// is `0`, same as `Instruction::SkipForward`.
+    JumpForward(usize),
+    /// Jump to the operation with the given index if the value at the pointer
+    /// is **not** `0`, same as `Instruction::SkipBackward`.
+    JumpBackward(usize),
+    /// Set the value at the pointer to `0`, from `[-]`.
+    SetZero,
+    /// Add the value at the pointer times a factor to the cell at an offset
+    /// from the pointer, as `(offset, factor)`. Always followed by a
+    /// `SetZero`, from loops like `[->+>--<<]`.
+    MulAdd(isize, isize),
+    /// Move the pointer up by the given stride until the value at the
+    /// pointer is `0`, from loops like `[>]`.
+    ScanRight(usize),
+    /// Move the pointer down by the given stride until the value at the
+    /// pointer is `0`, from loops like `[<<]`.
+    ScanLeft(usize),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Op::AddVal(n) if n < 0  => write!(f, "-{}", -n),
+            Op::AddVal(n)           => write!(f, "+{}", n),
+            Op::MovePtr(n) if n < 0 => write!(f, "<{}", -n),
+            Op::MovePtr(n)          => write!(f, ">{}", n),
+            Op::Output              => write!(f, "."),
+            Op::Input               => write!(f, ","),
+            Op::JumpForward(_)      => write!(f, "["),
+            Op::JumpBackward(_)     => write!(f, "]"),
+            Op::SetZero             => write!(f, "zero"),
+            Op::MulAdd(o, n)        => write!(f, "mul({},{})", o, n),
+            Op::ScanRight(n)        => write!(f, "scan>{}", n),
+            Op::ScanLeft(n)         => write!(f, "scan<{}", n),
+        }
+    }
+}
+
+/// The intermediate representation (IR) of a program, optimized for
+/// execution.
+///
+/// The IR is built from the ASL of a `Program`, and keeps the range of
+/// program counters of the instructions each operation replaces. This
+/// allows the interpreter to run the IR while reporting the same program
+/// counter and cycle count as running the program itself. A loop replaced
+/// by more than one operation is kept by the first of them, the others
+/// replace no instructions.
+///
+/// Only runs of the *same* instruction are folded, so `+-` is still two
+/// operations. Folding `+-` away would change the result of programs run on
+/// a tape that errors on overflow. For the same reason the only counting
+/// loops replaced are ones counting down by 1, for example `[-]` is
+/// replaced but `[+]` is not.
+///
+/// The loops replaced by `MulAdd` and the scans do depend on the tape, since
+/// they assume each move of the pointer can be undone, and reaches another
+/// cell. That isn't so on a tape which clamps the pointer at its ends, or
+/// wraps it around onto the counter, see `Tape::undoes_move`. The
+/// interpreter checks this before running them, and steps through the loop
+/// when the tape can't. Compiled code wraps the pointer, and is built with
+/// `Ir::wrapping` to leave those loops alone.
+#[derive(Debug, Default)]
+pub struct Ir {
+    ops: Vec<Op>,
+    ranges: Vec<Range<usize>>,
+}
+
+impl Ir {
+    /// Build the IR for the given program, folding runs of instructions and
+    /// replacing common loops.
+    pub fn new(program: &Program) -> Ir {
+        idiom::replace(Ir::fold(program), None)
+    }
+
+    /// Build the IR for the given program, to run on a tape of `length`
+    /// cells whose pointer wraps around at the ends. This is `Ir::new`,
+    /// except that loops moving all the way around onto their counter
+    /// aren't replaced.
+    pub fn wrapping(program: &Program, length: usize) -> Ir {
+        idiom::replace(Ir::fold(program), Some(length))
+    }
+
+    /// Build the IR for the given program, only folding runs of
+    /// instructions.
+    pub fn fold(program: &Program) -> Ir {
+        let mut ir = Ir::default();
+        for pc in 0..program.len() {
+            let op = match program.get(pc).expect("in") {
+                Instruction::IncVal => Op::AddVal(1),
+                Instruction::DecVal => Op::AddVal(-1),
+                Instruction::IncPtr => Op::MovePtr(1),
+                Instruction::DecPtr => Op::MovePtr(-1),
+                Instruction::Output => Op::Output,
+                Instruction::Input => Op::Input,
+                // Resolved once all the operations are known.
+                Instruction::SkipForward(_) => Op::JumpForward(0),
+                Instruction::SkipBackward(_) => Op::JumpBackward(0),
+            };
+            match (ir.ops.last_mut(), op) {
+                (Some(Op::AddVal(n)), Op::AddVal(m)) |
+                (Some(Op::MovePtr(n)), Op::MovePtr(m)) if n.signum() == m => {
+                    *n += m;
+                    ir.ranges.last_mut().expect("in").end += 1;
+                },
+                _ => ir.push(op, pc..pc + 1),
+            }
+        }
+        ir.link();
+        ir
+    }
+
+    /// Get the operation at the given index.
+    pub fn get(&self, index: usize) -> Option<Op> {
+        self.ops.get(index).copied()
+    }
+
+    /// The program counter of the first instruction the operation at the
+    /// given index replaces.
+    pub fn pc(&self, index: usize) -> Option<usize> {
+        self.ranges.get(index).map(|r| r.start)
+    }
+
+    /// The program counters of the instructions the operation at the given
+    /// index replaces.
+    pub fn replaces(&self, index: usize) -> Option<Range<usize>> {
+        self.ranges.get(index).cloned()
+    }
+
+    /// Find the operation replacing the instruction at the given program
+    /// counter. Returns the operation's index and how many of its
+    /// instructions come before the one at the program counter, or `None`
+    /// when the program counter is past the end.
+    pub fn locate(&self, pc: usize) -> Option<(usize, usize)> {
+        let mut index = self.ranges.partition_point(|r| r.start <= pc);
+        while index > 0 {
+            index -= 1;
+            let range = &self.ranges[index];
+            if range.contains(&pc) {
+                return Some((index, pc - range.start))
+            } else if !range.is_empty() {
+                break
+            }
+        }
+        None
+    }
+
+    /// The number of operations.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns true when there are no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    fn push(&mut self, op: Op, range: Range<usize>) {
+        self.ops.push(op);
+        self.ranges.push(range);
+    }
+
+    /// Point every jump at its matching jump, the program is known to have
+    /// matching brackets.
+    fn link(&mut self) {
+        let mut stack = Vec::new();
+        for index in 0..self.ops.len() {
+            match self.ops[index] {
+                Op::JumpForward(_) => stack.push(index),
+                Op::JumpBackward(_) => {
+                    let open = stack.pop().expect("brackets match");
+                    self.ops[open] = Op::JumpForward(index);
+                    self.ops[index] = Op::JumpBackward(open);
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a Program> for Ir {
+    fn from(program: &'a Program) -> Ir {
+        Ir::new(program)
+    }
+}
+
+impl fmt::Display for Ir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ops: Vec<String> = self.ops.iter().map(|op| op.to_string()).collect();
+        write!(f, "{}", ops.join(" "))
+    }
+}
+
+/// Replacing common loops with single operations.
+mod idiom;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(source: &str) -> Ir {
+        Ir::fold(&Program::parse(source).unwrap())
+    }
+
+    #[test]
+    fn fold_runs() {
+        let ir = fold("+++>>--<.,");
+        assert_eq!(ir.ops, vec![Op::AddVal(3), Op::MovePtr(2), Op::AddVal(-2),
+                                Op::MovePtr(-1), Op::Output, Op::Input]);
+        assert_eq!(ir.ranges, vec![0..3, 3..5, 5..7, 7..8, 8..9, 9..10]);
+    }
+
+    #[test]
+    fn no_fold_across_directions() {
+        let ir = fold("+-><");
+        assert_eq!(ir.ops, vec![Op::AddVal(1), Op::AddVal(-1),
+                                Op::MovePtr(1), Op::MovePtr(-1)]);
+    }
+
+    #[test]
+    fn jumps() {
+        let ir = fold("++[->+<]");
+        assert_eq!(ir.get(1), Some(Op::JumpForward(6)));
+        assert_eq!(ir.get(6), Some(Op::JumpBackward(1)));
+    }
+
+    #[test]
+    fn locate() {
+        let ir = fold("+++>.");
+        assert_eq!(ir.locate(0), Some((0, 0)));
+        assert_eq!(ir.locate(2), Some((0, 2)));
+        assert_eq!(ir.locate(3), Some((1, 0)));
+        assert_eq!(ir.locate(4), Some((2, 0)));
+        assert_eq!(ir.locate(5), None);
+    }
+
+    #[test]
+    fn locate_loop() {
+        let ir = Ir::new(&Program::parse("+[->+<].").unwrap());
+        assert_eq!(ir.locate(1), Some((1, 0)));
+        assert_eq!(ir.locate(4), Some((1, 3)));
+        assert_eq!(ir.locate(7), Some((3, 0)));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(fold("+++[-<<]").to_string(), "+3 [ -1 <2 ]");
+    }
+}
@@ -0,0 +1,232 @@
+use crate::ir::{Ir, Op};
+
+/// The exit status of compiled code which ran to the end.
+pub const OK: u32 = 0;
+
+/// Addresses of the functions called for IO. Both are called with the
+/// context passed to the compiled code, `output` with the value of the
+/// current cell, and `input` with a pointer to it. A non-zero return stops
+/// the compiled code, returning that value.
+pub struct Callbacks {
+    pub output: usize,
+    pub input: usize,
+}
+
+/// Assemble x86-64 machine code for the given IR, for a tape of `length`
+/// cells.
+///
+/// The code is a function following the System V calling convention, which
+/// takes a context pointer and the start and end of the tape, and returns
+/// `OK` or the status of a callback. Registers are used as follows.
+///
+/// - `rbx` points at the current cell.
+/// - `r12` and `r13` are the start and (exclusive) end of the tape.
+/// - `r14` is the context, for the callbacks.
+/// - `r15` is the length of the tape.
+///
+/// Cell values wrap, and so does the pointer at the ends of the tape.
+pub fn assemble(ir: &Ir, length: usize, callbacks: &Callbacks) -> Vec<u8> {
+    let mut asm = Assembler { length, ..Assembler::default() };
+    // push rbx, r12, r13, r14, r15. The fifth push keeps the stack aligned
+    // to 16 bytes for the calls.
+    asm.emit(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+    // mov r14, rdi; mov rbx, rsi; mov r12, rsi; mov r13, rdx
+    asm.emit(&[0x49, 0x89, 0xFE, 0x48, 0x89, 0xF3, 0x49, 0x89, 0xF4, 0x49, 0x89, 0xD5]);
+    // mov r15, r13; sub r15, r12
+    asm.emit(&[0x4D, 0x89, 0xEF, 0x4D, 0x29, 0xE7]);
+
+    let mut loops = Vec::new();
+    for index in 0..ir.len() {
+        match ir.get(index).expect("in") {
+            Op::AddVal(n) => asm.add_val(n),
+            Op::MovePtr(n) => asm.move_ptr(n),
+            Op::Output => {
+                // mov rdi, r14; movzx esi, byte [rbx]
+                asm.emit(&[0x4C, 0x89, 0xF7, 0x0F, 0xB6, 0x33]);
+                asm.call(callbacks.output);
+            },
+            Op::Input => {
+                // mov rdi, r14; mov rsi, rbx
+                asm.emit(&[0x4C, 0x89, 0xF7, 0x48, 0x89, 0xDE]);
+                asm.call(callbacks.input);
+            },
+            Op::JumpForward(_) => {
+                // cmp byte [rbx], 0; je <after the loop>
+                asm.emit(&[0x80, 0x3B, 0x00, 0x0F, 0x84]);
+                loops.push(asm.len());
+                asm.emit(&[0; 4]);
+            },
+            Op::JumpBackward(_) => {
+                let open = loops.pop().expect("brackets match");
+                // cmp byte [rbx], 0; jne <start of the loop>
+                asm.emit(&[0x80, 0x3B, 0x00, 0x0F, 0x85]);
+                let rel = asm.len();
+                asm.emit(&[0; 4]);
+                asm.patch(rel, open + 4);
+                let end = asm.len();
+                asm.patch(open, end);
+            },
+            Op::SetZero => {
+                // mov byte [rbx], 0
+                asm.emit(&[0xC6, 0x03, 0x00]);
+            },
+            Op::MulAdd(offset, factor) => {
+                // movzx eax, byte [rbx]; test al, al; jz <skip>
+                asm.emit(&[0x0F, 0xB6, 0x03, 0x84, 0xC0, 0x74, 0x00]);
+                let skip = asm.len();
+                // lea rcx, [rbx + offset]
+                let offset = asm.wrapped(offset);
+                asm.emit(&[0x48, 0x8D, 0x8B]);
+                asm.emit(&(offset as i32).to_le_bytes());
+                if offset > 0 {
+                    // cmp rcx, r13; jb <next>; sub rcx, r15
+                    asm.emit(&[0x4C, 0x39, 0xE9, 0x72, 0x03, 0x4C, 0x29, 0xF9]);
+                } else {
+                    // cmp rcx, r12; jae <next>; add rcx, r15
+                    asm.emit(&[0x4C, 0x39, 0xE1, 0x73, 0x03, 0x4C, 0x01, 0xF9]);
+                }
+                // imul eax, eax, factor; add byte [rcx], al
+                asm.emit(&[0x69, 0xC0]);
+                asm.emit(&(factor as i32).to_le_bytes());
+                asm.emit(&[0x00, 0x01]);
+                asm.code[skip - 1] = (asm.len() - skip) as u8;
+            },
+            Op::ScanRight(n) => asm.scan(n as isize),
+            Op::ScanLeft(n) => asm.scan(-(n as isize)),
+        }
+    }
+
+    // xor eax, eax
+    asm.emit(&[0x31, 0xC0]);
+    let ret = asm.len();
+    // pop r15, r14, r13, r12, rbx; ret
+    asm.emit(&[0x41, 0x5F, 0x41, 0x5E, 0x41, 0x5D, 0x41, 0x5C, 0x5B, 0xC3]);
+    // The callbacks leave their status in eax already, so they return
+    // straight away.
+    for i in 0..asm.exits.len() {
+        let at = asm.exits[i];
+        asm.patch(at, ret);
+    }
+    asm.code
+}
+
+#[derive(Default)]
+struct Assembler {
+    code: Vec<u8>,
+    /// The length of the tape.
+    length: usize,
+    /// Jumps out after a callback, by the position of their 32 bit relative
+    /// address.
+    exits: Vec<usize>,
+}
+
+impl Assembler {
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Set the 32 bit relative address at `at` to jump to `target`.
+    fn patch(&mut self, at: usize, target: usize) {
+        let rel = target as i64 - (at as i64 + 4);
+        self.code[at..at + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+    }
+
+    /// A move of `n` cells, without the whole trips around the tape. Moving
+    /// by less than the length only needs to wrap around once.
+    fn wrapped(&self, n: isize) -> isize {
+        n % self.length as isize
+    }
+
+    fn add_val(&mut self, n: isize) {
+        // add byte [rbx], n
+        self.emit(&[0x80, 0x03, n as u8]);
+    }
+
+    fn move_ptr(&mut self, n: isize) {
+        let n = self.wrapped(n);
+        // add rbx, n
+        self.emit(&[0x48, 0x81, 0xC3]);
+        self.emit(&(n as i32).to_le_bytes());
+        if n > 0 {
+            // cmp rbx, r13; jb <next>; sub rbx, r15
+            self.emit(&[0x4C, 0x39, 0xEB, 0x72, 0x03, 0x4C, 0x29, 0xFB]);
+        } else {
+            // cmp rbx, r12; jae <next>; add rbx, r15
+            self.emit(&[0x4C, 0x39, 0xE3, 0x73, 0x03, 0x4C, 0x01, 0xFB]);
+        }
+    }
+
+    fn scan(&mut self, n: isize) {
+        let top = self.len();
+        // cmp byte [rbx], 0; je <done>
+        self.emit(&[0x80, 0x3B, 0x00, 0x0F, 0x84]);
+        let done = self.len();
+        self.emit(&[0; 4]);
+        self.move_ptr(n);
+        // jmp <top>
+        self.emit(&[0xE9]);
+        let rel = self.len();
+        self.emit(&[0; 4]);
+        self.patch(rel, top);
+        let end = self.len();
+        self.patch(done, end);
+    }
+
+    fn call(&mut self, function: usize) {
+        // mov rax, function; call rax; test eax, eax; jnz <callback exit>
+        self.emit(&[0x48, 0xB8]);
+        self.emit(&(function as u64).to_le_bytes());
+        self.emit(&[0xFF, 0xD0, 0x85, 0xC0, 0x0F, 0x85]);
+        self.exits.push(self.len());
+        self.emit(&[0; 4]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Program;
+    use super::*;
+
+    fn assemble_source(source: &str) -> Vec<u8> {
+        let ir = Ir::wrapping(&Program::parse(source).unwrap(), 4);
+        assemble(&ir, 4, &Callbacks { output: 0, input: 0 })
+    }
+
+    #[test]
+    fn empty() {
+        let code = assemble_source("");
+        // Prologue, xor eax, eax and the epilogue.
+        assert_eq!(&code[27..29], &[0x31, 0xC0]);
+        assert_eq!(code[38], 0xC3);
+    }
+
+    #[test]
+    fn add_val() {
+        let code = assemble_source("---");
+        assert_eq!(&code[27..30], &[0x80, 0x03, 0xFD]);
+    }
+
+    #[test]
+    fn move_ptr() {
+        // Around the tape and one more, then back by one.
+        let code = assemble_source(">>>>><");
+        assert_eq!(&code[27..35], &[0x48, 0x81, 0xC3, 1, 0, 0, 0, 0x4C]);
+        assert_eq!(&code[35..42], &[0x39, 0xEB, 0x72, 0x03, 0x4C, 0x29, 0xFB]);
+        assert_eq!(&code[42..49], &[0x48, 0x81, 0xC3, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(&code[49..57], &[0x4C, 0x39, 0xE3, 0x73, 0x03, 0x4C, 0x01, 0xFB]);
+    }
+
+    #[test]
+    fn loop_jumps() {
+        let code = assemble_source("[+]");
+        // je to just after the loop.
+        assert_eq!(&code[27..32], &[0x80, 0x3B, 0x00, 0x0F, 0x84]);
+        assert_eq!(&code[32..36], &(3 + 9i32).to_le_bytes());
+        // jne back to the start of the body.
+        assert_eq!(&code[44..48], &(-(3 + 9i32)).to_le_bytes());
+    }
+}
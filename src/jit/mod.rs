@@ -0,0 +1,331 @@
+use std::{io, mem, ptr, slice};
+use std::io::{ErrorKind, Read, Write};
+use std::os::raw::c_void;
+use crate::{Eof, Error};
+use crate::ir::Ir;
+use crate::program::Program;
+use crate::tape::TAPE_LENGTH;
+
+/// A just in time (JIT) compiler, which runs programs as native x86-64
+/// machine code.
+///
+/// The JIT is used much like an `Interpreter`, with the same `load`,
+/// `read_from`, `write_to` and `on_eof` methods. Running a program compiles
+/// its optimized [`Ir`][ir] into an executable buffer, and calls into it.
+/// This is only available with the `jit` feature, on x86-64 Linux.
+///
+/// The compiled program has the semantics of the C from `codegen::C`, which
+/// are those of a `ModArrayTape` of `TAPE_LENGTH` cells, or the length given
+/// to `tape_length`. Values wrap around, and so does the pointer at the ends
+/// of the tape. Every run starts with a new tape. There is no cycle limit,
+/// programs which never halt will run forever.
+///
+/// ```
+/// use brainfuck::jit::Jit;
+/// use brainfuck::program::Program;
+///
+/// let program = Program::parse("++++++++[>++++++++<-]>+.").unwrap();
+/// let mut writer = Vec::<u8>::new();
+/// Jit::default().load(program).write_to(&mut writer).run().unwrap();
+/// assert_eq!(writer, b"A");
+/// ```
+///
+/// [ir]: ../ir/struct.Ir.html
+pub struct Jit<'a> {
+    program: Option<Program>,
+    reader: Option<&'a mut dyn Read>,
+    writer: Option<&'a mut dyn Write>,
+    eof: Eof,
+    tape_length: usize,
+}
+
+impl<'a> Default for Jit<'a> {
+    fn default() -> Self {
+        Jit {
+            program: None,
+            reader: None,
+            writer: None,
+            eof: Eof::default(),
+            tape_length: TAPE_LENGTH,
+        }
+    }
+}
+
+impl<'a> Jit<'a> {
+    /// Create a new JIT with the given program, reader, and writer.
+    pub fn new<R: Read, W: Write>(program: Program, reader: &'a mut R, writer: &'a mut W) -> Jit<'a> {
+        let mut jit = Self::default();
+        jit.load(program);
+        jit.read_from(reader);
+        jit.write_to(writer);
+        jit
+    }
+
+    /// Load a program for the JIT to run.
+    pub fn load(&mut self, program: Program) -> &mut Self {
+        self.program = Some(program);
+        self
+    }
+
+    /// Use the given reader for the `Input` instruction.
+    pub fn read_from<R: Read>(&mut self, reader: &'a mut R) -> &mut Self {
+        self.reader = Some(reader);
+        self
+    }
+
+    /// Use the given writer for the `Output` instruction.
+    pub fn write_to<W: Write>(&mut self, writer: &'a mut W) -> &mut Self {
+        self.writer = Some(writer);
+        self
+    }
+
+    /// Use the given convention for the `Input` instruction when there is
+    /// no more input to read.
+    pub fn on_eof(&mut self, eof: Eof) -> &mut Self {
+        self.eof = eof;
+        self
+    }
+
+    /// Use a tape with the given number of cells, `TAPE_LENGTH` by default.
+    /// There must be at least one.
+    pub fn tape_length(&mut self, length: usize) -> &mut Self {
+        assert!(length > 0, "a tape needs a cell");
+        self.tape_length = length;
+        self
+    }
+
+    /// Compile and run the program.
+    pub fn run(&mut self) -> Result<(), Error> {
+        let ir = match self.program {
+            Some(ref p) => Ir::wrapping(p, self.tape_length),
+            None => return Err(Error::NoProgram),
+        };
+        let callbacks = asm::Callbacks {
+            output: output as *const () as usize,
+            input: input as *const () as usize,
+        };
+        let code = Code::new(&asm::assemble(&ir, self.tape_length, &callbacks))?;
+        let mut tape = vec![0u8; self.tape_length];
+        let mut context = Context {
+            reader: self.reader.as_mut().map(|r| &mut **r as &mut dyn Read),
+            writer: self.writer.as_mut().map(|w| &mut **w as &mut dyn Write),
+            eof: self.eof,
+            error: None,
+        };
+        let range = tape.as_mut_ptr_range();
+        let status = code.call(&mut context as *mut Context as *mut c_void, range.start, range.end);
+        match status {
+            asm::OK => Ok(()),
+            _ => Err(context.error.take().expect("callbacks set an error")),
+        }
+    }
+}
+
+/// The state the callbacks from compiled code need.
+struct Context<'a> {
+    reader: Option<&'a mut dyn Read>,
+    writer: Option<&'a mut dyn Write>,
+    eof: Eof,
+    error: Option<Error>,
+}
+
+impl<'a> Context<'a> {
+    fn output(&mut self, value: u8) -> Result<(), Error> {
+        if let Some(ref mut w) = self.writer {
+            w.write_all(&[value])?;
+        }
+        Ok(())
+    }
+
+    fn input(&mut self, cell: &mut u8) -> Result<(), Error> {
+        let mut buf = [0];
+        let read = match self.reader {
+            Some(ref mut r) => loop {
+                match r.read(&mut buf) {
+                    Ok(n) => break n,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {},
+                    Err(e) => return Err(Error::Io(e)),
+                }
+            },
+            None => 0,
+        };
+        if read > 0 {
+            *cell = buf[0];
+            return Ok(())
+        }
+        match self.eof {
+            Eof::Unchanged => {},
+            Eof::Zero => *cell = 0,
+            Eof::AllOnes => *cell = !0,
+            Eof::Error => return Err(Error::Eof),
+        }
+        Ok(())
+    }
+
+    /// Turn a result into an exit status for the compiled code.
+    fn status(&mut self, result: Result<(), Error>) -> u32 {
+        match result {
+            Ok(()) => asm::OK,
+            Err(e) => {
+                self.error = Some(e);
+                u32::MAX
+            },
+        }
+    }
+}
+
+extern "C" fn output(context: *mut c_void, value: u8) -> u32 {
+    // Safety: the compiled code passes back the context it was called with.
+    let context = unsafe { &mut *(context as *mut Context) };
+    let result = context.output(value);
+    context.status(result)
+}
+
+extern "C" fn input(context: *mut c_void, cell: *mut u8) -> u32 {
+    // Safety: the compiled code passes back the context it was called with,
+    // and a pointer to a cell of the tape.
+    let context = unsafe { &mut *(context as *mut Context) };
+    let result = context.input(unsafe { &mut *cell });
+    context.status(result)
+}
+
+/// Executable memory holding compiled code.
+struct Code {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+type Function = extern "C" fn(*mut c_void, *mut u8, *mut u8) -> u32;
+
+impl Code {
+    /// Map the given machine code into executable memory.
+    fn new(code: &[u8]) -> io::Result<Code> {
+        let len = code.len();
+        // Safety: a new anonymous mapping is only ever accessed through the
+        // returned `Code`, and only executable after being written.
+        unsafe {
+            let ptr = libc::mmap(ptr::null_mut(), len,
+                                 libc::PROT_READ | libc::PROT_WRITE,
+                                 libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                                 -1, 0);
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error())
+            }
+            let mapped = Code { ptr, len };
+            slice::from_raw_parts_mut(ptr as *mut u8, len).copy_from_slice(code);
+            if libc::mprotect(ptr, len, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return Err(io::Error::last_os_error())
+            }
+            Ok(mapped)
+        }
+    }
+
+    /// Call the compiled code with the given context and tape.
+    fn call(&self, context: *mut c_void, start: *mut u8, end: *mut u8) -> u32 {
+        // Safety: the code was assembled as a `Function`, which only
+        // accesses the tape between `start` and `end`.
+        unsafe {
+            let function: Function = mem::transmute(self.ptr);
+            function(context, start, end)
+        }
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        // Safety: the mapping is no longer used.
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// Generating machine code.
+mod asm;
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use super::*;
+
+    fn run(source: &str, input: &str, eof: Eof) -> (Result<(), Error>, Vec<u8>) {
+        let mut reader = input.as_bytes();
+        let mut writer = Vec::<u8>::new();
+        let result = {
+            let program = Program::parse(source).unwrap();
+            let mut jit = Jit::new(program, &mut reader, &mut writer);
+            jit.on_eof(eof).run()
+        };
+        (result, writer)
+    }
+
+    #[test]
+    fn new() {
+        let program = Program::parse("++>+.").unwrap();
+        let mut reader = io::empty();
+        let mut writer = Vec::<u8>::new();
+        let _ = Jit::new(program, &mut reader, &mut writer);
+    }
+
+    #[test]
+    fn no_program() {
+        assert!(matches!(Jit::default().run(), Err(Error::NoProgram)));
+    }
+
+    #[test]
+    fn output() {
+        let (result, output) = run("++++++++[>++++++++<-]>+.+.", "", Eof::Unchanged);
+        result.unwrap();
+        assert_eq!(output, b"AB");
+    }
+
+    #[test]
+    fn wrapping() {
+        let (result, output) = run("-.+.", "", Eof::Unchanged);
+        result.unwrap();
+        assert_eq!(output, [255, 0]);
+    }
+
+    #[test]
+    fn input() {
+        let (result, output) = run(",+.,.", "a", Eof::Zero);
+        result.unwrap();
+        assert_eq!(output, b"b\0");
+    }
+
+    #[test]
+    fn eof_error() {
+        let (result, _) = run(",", "", Eof::Error);
+        assert!(matches!(result, Err(Error::Eof)));
+    }
+
+    #[test]
+    fn ptr_wraps() {
+        let (result, output) = run("+<+.>.", "", Eof::Unchanged);
+        result.unwrap();
+        assert_eq!(output, [1, 1]);
+        // Scanning past the end of the tape.
+        let (result, output) = run("<+[>]<.", "", Eof::Unchanged);
+        result.unwrap();
+        assert_eq!(output, [1]);
+    }
+
+    #[test]
+    fn tape_length() {
+        let mut writer = Vec::<u8>::new();
+        let mut jit = Jit::default();
+        jit.write_to(&mut writer).tape_length(3);
+        jit.load(Program::parse("+>>>+.<<<<<<.").unwrap()).run().unwrap();
+        // Adding to the cell three along adds to the counter, so the loop
+        // only runs once.
+        jit.load(Program::parse("-[->+>>++<<<]>.").unwrap()).run().unwrap();
+        assert_eq!(writer, [2, 2, 1]);
+    }
+
+    #[test]
+    fn idioms() {
+        let (result, output) = run("+++++[>+++<-]>[>++<-]>[<]>.>[>]<.", "", Eof::Unchanged);
+        result.unwrap();
+        assert_eq!(output, [30, 30]);
+    }
+}
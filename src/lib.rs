@@ -53,15 +53,17 @@
 //! 6. Programs containing unmatching brackets.
 //!
 //! For 1-4 see the tape's [documentation][tape]. New tape's can be created to
-//! give arbitrary semantics for these points. For 5 and 6, attempts to read
-//! when there are no more input values are ignored. Programs with unmatched
-//! brackets are invalid.
+//! give arbitrary semantics for these points. For 5, attempts to read when
+//! there are no more input values leave the cell unchanged by default, other
+//! conventions can be chosen with [`Interpreter::on_eof`][eof]. Programs with
+//! unmatched brackets are invalid.
 //!
 //! [instruction]: enum.Instruction.html
 //! [brainfuck]: http://www.muppetlabs.com/~breadbox/bf/
 //! [control-flow]: enum.Instruction.html#control-flow
 //! [instruction-docs]: enum.Instruction.html
 //! [tape]: tape/index.html
+//! [eof]: struct.Interpreter.html#method.on_eof
 #![deny(warnings)]
 
 use std::io;
@@ -69,14 +71,17 @@ use std::path::Path;
 use tape::VecTape;
 use program::Program;
 
-/// The number of instructions allowed to execute before the interpreter
-/// errors with `Error::CycleLimit`.
+/// The default number of instructions allowed to execute before the
+/// interpreter errors with `Error::CycleLimit`, see
+/// `Interpreter::cycle_limit` to change it.
 pub const CYCLE_LIMIT: u64 = 10_000_000;
 
 // Re-exports.
-pub use error::Error;
+pub use eof::Eof;
+pub use error::{Error, RuntimeError};
 pub use interpreter::Interpreter;
 pub use instruction::Instruction;
+pub use status::Status;
 
 /// Run the given program with STDIN and STDOUT as the IO buffers.
 fn eval(program: Program) -> Result<(), Error> {
@@ -114,6 +119,9 @@ pub fn eval_file<P: AsRef<Path>>(path: P) -> Result<(), Error> {
     eval(program)
 }
 
+/// What to do when there is nothing left to read.
+mod eof;
+
 /// Brainfuck errors are the best kind of errors.
 mod error;
 
@@ -123,8 +131,30 @@ mod interpreter;
 /// Brainfuck instructions are the best kind of instructions.
 mod instruction;
 
+/// Where an interpreter is up to.
+mod status;
+
 /// Data structure for the logic of a user brainfuck program.
 pub mod program;
 
+/// Optimized representation of programs for faster execution.
+pub mod ir;
+
+/// Stepping through programs interactively.
+pub mod debugger;
+
+/// Running brainfuck a line at a time, on the same tape.
+pub mod repl;
+
+/// Finding where programs spend their time.
+pub mod profiler;
+
+/// Transpiling programs to other languages.
+pub mod codegen;
+
+/// Compiling programs to native code at runtime.
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub mod jit;
+
 /// Underlying data structure for brainfuck programs.
 pub mod tape;
@@ -1,64 +1,430 @@
-extern crate rustc_serialize;
 extern crate docopt;
 extern crate brainfuck;
 
-use std::io;
-use std::collections::HashMap;
-use docopt::Docopt;
-use brainfuck::{Interpreter, Instruction};
-use brainfuck::tape::ModArrayTape;
+use std::{fs, io, process};
+use std::fmt::Display;
+use std::io::Write;
+use std::fs::File;
+use std::str::FromStr;
+use docopt::{ArgvMap, Docopt};
+use brainfuck::{CYCLE_LIMIT, Eof, Error, Interpreter};
+use brainfuck::tape::{ArrayTape, BigTape, InfiniteTape, Integer, Kind, ModArrayTape, PointerPolicy,
+                     PolicyTape, SparseTape, Tape, TAPE_LENGTH, ValuePolicy, VecTape};
+use brainfuck::ir::Ir;
+use brainfuck::codegen::{Brainfuck, C};
+use brainfuck::debugger::Debugger;
+use brainfuck::profiler::Profiler;
+use brainfuck::repl::Repl;
 use brainfuck::program::Program;
 
-const USAGE: &'static str = "
+const USAGE: &str = "
 Brainfuck
 
 Usage:
-    brainfuck [options] <file>
-    brainfuck [options] -e <program>
+    brainfuck run [options] (<file> | -e <program>)
+    brainfuck compile [options] (<file> | -e <program>)
+    brainfuck fmt [options] (<file> | -e <program>)
+    brainfuck check [options] (<file> | -e <program>)
+    brainfuck debug [options] (<file> | -e <program>)
+    brainfuck profile [options] (<file> | -e <program>)
+    brainfuck repl [options]
+    brainfuck [options] (<file> | -e <program>)
+
+Commands:
+    run                   Run the program, this is the default when no
+                          command is given.
+    compile               Don't run, print the program transpiled to C, or
+                          another form chosen with --emit.
+    fmt                   Don't run, print the program as brainfuck with a
+                          consistent layout and the comments removed.
+    check                 Don't run, check the program parses and print
+                          warnings for likely mistakes to STDERR.
+    debug                 Step through the program interactively, type
+                          `help` at the prompt for the commands.
+    profile               Run the program, then report the source with the
+                          instructions executed on each line, and the loops
+                          with the most cycles, to STDERR.
+    repl                  Run each line typed at the prompt as a program,
+                          keeping the tape from one line to the next, type
+                          `:help` at the prompt for the commands. The tape
+                          is a policy tape behaving like --tape, which
+                          can't be big.
 
 Options:
-    -a --asl              Don't run, simply print the ASL.
-    -i --instrumentation  Enable program instrumentation.
+    --tape=<kind>         The tape to run on, one of mod-array, vec, array,
+                          big, infinite, sparse or policy, see below
+                          [default: mod-array].
+    --eof=<mode>          What `,` does at the end of input, one of
+                          unchanged, zero, ones or error [default: unchanged].
+    --cycle-limit=<n>     Stop after executing <n> instructions, or never
+                          when <n> is `none` [default: {cycle_limit}]. In
+                          the REPL the limit is for each line.
+    --tape-length=<n>     The number of cells on the tape
+                          [default: {tape_length}].
+    --cell-width=<bits>   The width of the cells, one of 8, 16, 32 or 64
+                          [default: 8].
+    --values=<policy>     What the policy tape does when a value goes out of
+                          range, one of wrap, saturate or error
+                          [default: error].
+    --pointer=<policy>    What the policy tape does when the pointer goes
+                          off either end, one of wrap, clamp, error or grow
+                          [default: error].
+
+Run options:
+    -O --optimize         Run the optimized IR of the program.
+    --jit                 Run the program as native code, this needs the
+                          `jit` feature, the mod-array tape and 8 bit cells.
+
+Profile options:
+    --top=<n>             The number of loops to list [default: 10].
+    --format=<format>     How to write the report, one of text, json or csv
+                          [default: text]. The fields of json and csv are
+                          listed in the docs of `brainfuck::profiler`, and
+                          only ever added to.
+    --report=<path>       Write the report to <path> instead of STDERR.
+
+Compile and fmt options:
+    --emit=<form>         What to compile the program to, one of c, asl or
+                          ir [default: c].
+    -o --output=<path>    Write to <path> instead of STDOUT.
+    --indent=<n>          Indent loops by <n> spaces when formatting
+                          [default: 4].
+    --width=<n>           Keep formatted lines to <n> characters
+                          [default: 80].
+
+Tapes:
+    mod-array             Values wrap around, and so does the pointer at the
+                          ends of the tape, like compiled C and the JIT.
+    vec                   Values and the pointer going out of range are
+                          errors, like the library's `eval_string`. Cells
+                          are allocated as the pointer reaches them.
+    array                 Like vec, with every cell allocated up front.
+    big                   Values never overflow, and --cell-width is
+                          ignored. The pointer going out of range is an
+                          error.
+    infinite              Values going out of range are errors, and the tape
+                          grows in both directions, --tape-length is
+                          ignored.
+    sparse                Like vec, storing only the cells that aren't 0.
+    policy                Chosen with --values and --pointer.
+
+Exit status:
+    0                     The program ran to the end.
+    1                     The arguments are invalid.
+    2                     The program doesn't parse.
+    3                     The program failed while running.
+    4                     The program hit the cycle limit.
+    5                     Reading or writing failed.
 ";
 
-#[derive(Debug, RustcDecodable)]
-struct Args {
-    arg_program: Option<String>,
-    arg_file: Option<String>,
-    flag_asl: bool,
-    flag_instrumentation: bool,
+/// Exit codes, see the usage.
+const EXIT_USAGE: i32 = 1;
+const EXIT_PARSE: i32 = 2;
+const EXIT_RUNTIME: i32 = 3;
+const EXIT_CYCLE_LIMIT: i32 = 4;
+const EXIT_IO: i32 = 5;
+
+/// The options shared by the commands.
+struct Settings {
+    tape: Kind,
+    values: ValuePolicy,
+    pointer: PointerPolicy,
+    eof: Eof,
+    cycle_limit: Option<u64>,
+    tape_length: usize,
+    cell_width: u32,
 }
 
 fn main() {
-    let args: Args = Docopt::new(USAGE)
-        .and_then(|d| d.decode())
+    let usage = USAGE.replace("{cycle_limit}", &CYCLE_LIMIT.to_string())
+        .replace("{tape_length}", &TAPE_LENGTH.to_string());
+    let args = Docopt::new(usage)
+        .and_then(|d| d.parse())
         .unwrap_or_else(|e| e.exit());
-    let program = (match args {
-        Args { arg_program: Some(p), .. } => Program::parse(&p),
-        Args { arg_file: Some(p), .. } => Program::from_file(p),
-        _ => panic!("Bad args."),
+    let settings = Settings {
+        tape: choice(&args, "--tape"),
+        values: choice(&args, "--values"),
+        pointer: choice(&args, "--pointer"),
+        eof: choice(&args, "--eof"),
+        cycle_limit: match args.get_str("--cycle-limit") {
+            "none" => None,
+            _ => Some(option(&args, "--cycle-limit", "cycle limit")),
+        },
+        tape_length: match option(&args, "--tape-length", "tape length") {
+            0 => fail(EXIT_USAGE, "Invalid tape length `0`: a tape needs a cell"),
+            n => n,
+        },
+        cell_width: match option(&args, "--cell-width", "cell width") {
+            bits @ 8 | bits @ 16 | bits @ 32 | bits @ 64 => bits,
+            _ => fail(EXIT_USAGE, format!("Invalid cell width `{}`", args.get_str("--cell-width"))),
+        },
+    };
+    if args.get_bool("repl") {
+        let (values, pointer) = match settings.tape {
+            Kind::Policy => (settings.values, settings.pointer),
+            kind => kind.policies().unwrap_or_else(|| {
+                fail(EXIT_USAGE, format!("The REPL can't run on the {} tape", kind));
+            }),
+        };
+        match settings.cell_width {
+            8 => repl::<u8>(&args, &settings, values, pointer),
+            16 => repl::<u16>(&args, &settings, values, pointer),
+            32 => repl::<u32>(&args, &settings, values, pointer),
+            _ => repl::<u64>(&args, &settings, values, pointer),
+        }
+        return;
+    }
+    let program = load(&args);
+    if args.get_bool("compile") {
+        match args.get_str("--emit") {
+            "c" => {
+                let mut c = C::default();
+                c.tape_length(settings.tape_length)
+                    .cell_width(settings.cell_width)
+                    .on_eof(settings.eof);
+                output(args.get_str("--output"), &mut io::stdout(), |mut out| c.write(&program, &mut out));
+            },
+            "asl" => output(args.get_str("--output"), &mut io::stdout(), |out| writeln!(out, "{}", program)),
+            "ir" => output(args.get_str("--output"), &mut io::stdout(), |out| writeln!(out, "{}", Ir::new(&program))),
+            form => fail(EXIT_USAGE, format!("Unknown form `{}`", form)),
+        }
+    } else if args.get_bool("fmt") {
+        let mut brainfuck = Brainfuck::default();
+        brainfuck.indent(option(&args, "--indent", "indent"))
+            .width(option(&args, "--width", "width"));
+        output(args.get_str("--output"), &mut io::stdout(), |mut out| brainfuck.write(&program, &mut out));
+    } else if args.get_bool("check") {
+        let name = program.name().unwrap_or("<program>");
+        for warning in program.lint() {
+            eprint!("{}", warning.render(name, program.source()));
+        }
+    } else if args.get_bool("--jit") && !args.get_bool("debug") && !args.get_bool("profile") {
+        if settings.tape != Kind::ModArray || settings.cell_width != 8 {
+            fail(EXIT_USAGE, "The JIT only runs on the mod-array tape with 8 bit cells");
+        }
+        run_jit(program, settings.eof, settings.tape_length);
+    } else {
+        interpret(&args, program, &settings);
+    }
+}
+
+/// Parse the value of an option naming one of a few choices, exiting when
+/// it's unknown.
+fn choice<T: FromStr<Err=String>>(args: &ArgvMap, name: &str) -> T {
+    args.get_str(name).parse().unwrap_or_else(|e| {
+        fail(EXIT_USAGE, e);
+    })
+}
+
+/// Parse the value of a numeric option, exiting when it's invalid.
+fn option<T: FromStr>(args: &ArgvMap, name: &str, what: &str) -> T
+where T::Err: Display {
+    let value = args.get_str(name);
+    value.parse().unwrap_or_else(|e| {
+        fail(EXIT_USAGE, format!("Invalid {} `{}`: {}", what, value, e));
+    })
+}
+
+/// Read and parse the program given as a file or with `-e`, exiting when
+/// it can't be.
+fn load(args: &ArgvMap) -> Program {
+    let (name, source) = if args.get_bool("-e") {
+        ("<program>", args.get_str("<program>").to_owned())
+    } else {
+        let path = args.get_str("<file>");
+        (path, fs::read_to_string(path).unwrap_or_else(|e| {
+            fail(EXIT_IO, format!("{}: {}", path, e));
+        }))
+    };
+    let mut program = Program::parse(&source).unwrap_or_else(|e| {
+        eprint!("{}", e.render(name, &source));
+        process::exit(EXIT_PARSE);
+    });
+    program.set_name(name);
+    program
+}
+
+/// Write to the file at `path`, or to `default` when there's no path.
+fn output<F>(path: &str, default: &mut dyn Write, write: F)
+where F: FnOnce(&mut dyn Write) -> io::Result<()> {
+    (match path {
+        "" => write(default),
+        path => File::create(path).and_then(|mut f| write(&mut f)),
     }).unwrap_or_else(|e| {
-        panic!("{}", e);
+        fail(EXIT_IO, e);
     });
-    if args.flag_asl {
-        println!("{}", program);
+}
+
+/// Run, debug or profile the program, on the chosen tape with cells of the
+/// chosen width.
+fn interpret(args: &ArgvMap, program: Program, settings: &Settings) {
+    match settings.cell_width {
+        8 => interpret_cells::<u8>(args, program, settings),
+        16 => interpret_cells::<u16>(args, program, settings),
+        32 => interpret_cells::<u32>(args, program, settings),
+        _ => interpret_cells::<u64>(args, program, settings),
+    }
+}
+
+/// Run, debug or profile the program, on the chosen tape with cells of type `C`.
+fn interpret_cells<C: Integer>(args: &ArgvMap, program: Program, settings: &Settings) {
+    let length = settings.tape_length;
+    match settings.tape {
+        Kind::Vec => interpret_on(args, program, settings, VecTape::<C>::with_length(length)),
+        Kind::Array => interpret_on(args, program, settings, ArrayTape::<C>::with_length(length)),
+        Kind::ModArray => interpret_on(args, program, settings, ModArrayTape::<C>::with_length(length)),
+        Kind::Big => interpret_on(args, program, settings, BigTape::with_length(length)),
+        Kind::Infinite => interpret_on(args, program, settings, InfiniteTape::<C>::default()),
+        Kind::Sparse => interpret_on(args, program, settings, SparseTape::<C>::with_length(length)),
+        Kind::Policy => {
+            let mut tape = PolicyTape::<C>::with_length(length);
+            tape.values(settings.values).pointer(settings.pointer);
+            interpret_on(args, program, settings, tape)
+        },
+    }
+}
+
+/// Run, debug or profile the program, on the given tape.
+fn interpret_on<T: Tape + Default>(args: &ArgvMap, program: Program, settings: &Settings, tape: T) {
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut interp = Interpreter::<T>::default();
+    interp.read_from(&mut stdin)
+        .write_to(&mut stdout)
+        .use_tape(tape)
+        .on_eof(settings.eof)
+        .cycle_limit(settings.cycle_limit)
+        .optimize(args.get_bool("--optimize"));
+    interp.load(program);
+    if args.get_bool("debug") {
+        debug(Debugger::new(interp));
+    } else if args.get_bool("profile") {
+        let format = args.get_str("--format");
+        if !["text", "json", "csv"].contains(&format) {
+            fail(EXIT_USAGE, format!("Unknown profile format `{}`", format));
+        }
+        let top = option(args, "--top", "number of loops");
+        let mut profiler = Profiler::new(interp);
+        let result = profiler.run();
+        let _ = io::stdout().flush();
+        output(args.get_str("--report"), &mut io::stderr(), |mut out| match format {
+            "json" => profiler.write_json(&mut out),
+            "csv" => profiler.write_csv(&mut out),
+            _ => write!(out, "{}\n{}", profiler.listing(), profiler.top(top)),
+        });
+        result.unwrap_or_else(|e| fail_run(e));
     } else {
-        let mut stdin = io::stdin();
-        let mut stdout = io::stdout();
-        let mut interp = Interpreter::<ModArrayTape>::new(program, &mut stdin, &mut stdout);
-        if args.flag_instrumentation {
-            let mut instruction_map: HashMap<Instruction, usize> = HashMap::new();
-            interp.run_with_callback(|_, i| {
-                let counter = instruction_map.entry(*i).or_insert(0);
-                *counter += 1;
-            }).unwrap_or_else(|e| {
-                panic!("{}", e);
-            });
-            println!("{:?}", instruction_map);
-        } else {
-            interp.run().unwrap_or_else(|e| {
-                panic!("{}", e);
-            });
+        interp.run().unwrap_or_else(|e| fail_run(e));
+    }
+}
+
+/// Print an error to STDERR and exit with the given code. Output of the
+/// program so far is flushed first.
+fn fail<D: Display>(code: i32, message: D) -> ! {
+    let _ = io::stdout().flush();
+    eprintln!("error: {}", message);
+    process::exit(code)
+}
+
+/// Exit for an error from running a program, with the code for its kind.
+fn fail_run(e: Error) -> ! {
+    let code = match *e.cause() {
+        Error::Io(_) => EXIT_IO,
+        Error::CycleLimit => EXIT_CYCLE_LIMIT,
+        _ => EXIT_RUNTIME,
+    };
+    fail(code, e)
+}
+
+/// Read debugger commands from STDIN until `quit` or the end of input. The
+/// program reads from STDIN as well, so its input is typed at the prompt.
+fn debug<T: Tape + Default>(mut debugger: Debugger<T>) {
+    let mut out = io::stdout();
+    loop {
+        print!("(bf) ");
+        out.flush().unwrap_or_else(|e| {
+            fail(EXIT_IO, e);
+        });
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {},
+            Err(e) => fail(EXIT_IO, e),
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match line.parse() {
+            Ok(command) => debugger.execute(command, &mut out),
+            Err(e) => writeln!(out, "{}", e).map(|_| true),
+        };
+        match result {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => fail(EXIT_IO, e),
         }
     }
 }
+
+/// Read REPL commands from STDIN until `:quit` or the end of input, running
+/// on a policy tape with cells of type `C`. Programs read from STDIN as
+/// well, so their input is typed at the prompt.
+fn repl<C: Integer>(args: &ArgvMap, settings: &Settings, values: ValuePolicy, pointer: PointerPolicy) {
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut tape = PolicyTape::<C>::with_length(settings.tape_length);
+    tape.values(values).pointer(pointer);
+    let mut interp = Interpreter::default();
+    interp.read_from(&mut stdin)
+        .write_to(&mut stdout)
+        .use_tape(tape)
+        .on_eof(settings.eof)
+        .optimize(args.get_bool("--optimize"));
+    let mut repl = Repl::new(interp);
+    repl.cycle_limit(settings.cycle_limit);
+    let mut out = io::stdout();
+    loop {
+        print!("> ");
+        out.flush().unwrap_or_else(|e| {
+            fail(EXIT_IO, e);
+        });
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => {
+                // End the prompt's line.
+                println!();
+                break
+            },
+            Ok(_) => {},
+            Err(e) => fail(EXIT_IO, e),
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match line.parse() {
+            Ok(command) => repl.execute(command, &mut out),
+            Err(e) => writeln!(out, "{}", e).map(|_| true),
+        };
+        match result {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => fail(EXIT_IO, e),
+        }
+    }
+}
+
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+fn run_jit(program: Program, eof: Eof, tape_length: usize) {
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+    brainfuck::jit::Jit::new(program, &mut stdin, &mut stdout)
+        .on_eof(eof)
+        .tape_length(tape_length)
+        .run()
+        .unwrap_or_else(|e| fail_run(e));
+}
+
+#[cfg(not(all(feature = "jit", target_arch = "x86_64", target_os = "linux")))]
+fn run_jit(_: Program, _: Eof, _: usize) {
+    fail(EXIT_USAGE, "Built without JIT support, enable the `jit` feature");
+}
@@ -0,0 +1,444 @@
+use std::io;
+use std::fmt::Write;
+use crate::{Error, Instruction, Interpreter, Status};
+use crate::program::{Position, Program};
+use crate::tape::Tape;
+
+/// Runs a program, counting how often each instruction executes, to find
+/// where the program spends its time.
+///
+/// Every instruction of a program comes from one position in the source,
+/// so the counts are per position as well. Loops are reported with the
+/// number of times they were entered, the iterations of their body, and
+/// the cycles spent inside them, counting those of any loops within.
+///
+/// Profiling steps through the program, so the optimized IR is never used.
+///
+/// Besides the text reports, `listing` and `top`, the counts can be written
+/// as JSON or CSV for other tools, with `write_json` and `write_csv`. Their
+/// fields are only ever added to, and `SCHEMA` is raised when they are.
+///
+/// ```
+/// use brainfuck::Interpreter;
+/// use brainfuck::program::Program;
+/// use brainfuck::profiler::Profiler;
+/// use brainfuck::tape::VecTape;
+///
+/// let mut interp = Interpreter::<VecTape>::default();
+/// interp.load(Program::parse("+++[>++[-]<-]").unwrap());
+/// let mut profiler = Profiler::new(interp);
+/// profiler.run().unwrap();
+/// let outer = profiler.loops()[0];
+/// assert_eq!((outer.entries, outer.iterations, outer.cycles), (1, 3, 34));
+/// ```
+pub struct Profiler<'a, T: Tape> {
+    interp: Interpreter<'a, T>,
+    counts: Vec<u64>,
+    iterations: Vec<u64>,
+    entries: Vec<u64>,
+}
+
+/// The version of the fields written by `Profiler::write_json` and
+/// `Profiler::write_csv`.
+pub const SCHEMA: u32 = 1;
+
+/// The number of times an instruction was executed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Count {
+    /// The program counter of the instruction.
+    pub pc: usize,
+    /// The instruction.
+    pub instruction: Instruction,
+    /// The position of the instruction in the source.
+    pub position: Position,
+    /// The number of times it was executed.
+    pub count: u64,
+}
+
+/// How a loop of the program ran.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Loop {
+    /// The program counter of the loop's `[`.
+    pub start: usize,
+    /// The program counter of the loop's `]`.
+    pub end: usize,
+    /// The position of the loop's `[` in the source.
+    pub position: Position,
+    /// The number of times the body was run from the `[`.
+    pub entries: u64,
+    /// The number of times the body was run, from the `[` or the `]`.
+    pub iterations: u64,
+    /// The cycles spent executing the loop, including its brackets and
+    /// every loop inside it.
+    pub cycles: u64,
+}
+
+impl<'a, T: Tape + Default> Profiler<'a, T> {
+    /// Create a profiler for the given interpreter, which must have a
+    /// program loaded.
+    pub fn new(interp: Interpreter<'a, T>) -> Profiler<'a, T> {
+        let len = interp.program().map_or(0, Program::len);
+        Profiler {
+            interp,
+            counts: vec![0; len],
+            iterations: vec![0; len],
+            entries: vec![0; len],
+        }
+    }
+
+    /// The interpreter being profiled.
+    pub fn interpreter(&self) -> &Interpreter<'a, T> {
+        &self.interp
+    }
+
+    /// Run the program to the end, counting each instruction executed. On
+    /// an error, what ran before it is still counted.
+    pub fn run(&mut self) -> Result<(), Error> {
+        loop {
+            let pc = self.interp.pc();
+            let instruction = self.interp.program().and_then(|p| p.get(pc));
+            match self.interp.step() {
+                Status::Error(e) => return Err(e),
+                _ if instruction.is_none() => return Ok(()),
+                _ => {},
+            }
+            self.counts[pc] += 1;
+            // The body of a loop starts right after one of its brackets.
+            match instruction {
+                Some(Instruction::SkipForward(_)) if self.interp.pc() == pc + 1 => {
+                    self.entries[pc] += 1;
+                    self.iterations[pc] += 1;
+                },
+                Some(Instruction::SkipBackward(start)) if self.interp.pc() == start + 1 => {
+                    self.iterations[start] += 1;
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// The total number of cycles profiled.
+    pub fn cycles(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The count of every instruction, in the order of the program.
+    pub fn counts(&self) -> Vec<Count> {
+        let program = self.program();
+        self.counts.iter().enumerate().map(|(pc, &count)| Count {
+            pc,
+            instruction: program.get(pc).expect("in"),
+            position: program.position(pc).expect("in"),
+            count,
+        }).collect()
+    }
+
+    /// Every loop of the program, in the order their `[` appear.
+    pub fn loops(&self) -> Vec<Loop> {
+        let program = self.program();
+        (0..program.len()).filter_map(|start| match program.get(start) {
+            Some(Instruction::SkipForward(end)) => Some(Loop {
+                start,
+                end,
+                position: program.position(start).expect("in"),
+                entries: self.entries[start],
+                iterations: self.iterations[start],
+                cycles: self.counts[start..=end].iter().sum(),
+            }),
+            _ => None,
+        }).collect()
+    }
+
+    /// Render the source with the number of instructions executed on each
+    /// line in a column before it. Lines without instructions have no
+    /// count.
+    ///
+    /// ```
+    /// use brainfuck::Interpreter;
+    /// use brainfuck::program::Program;
+    /// use brainfuck::profiler::Profiler;
+    /// use brainfuck::tape::VecTape;
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// interp.load(Program::parse("Count down\n+++\n[-]").unwrap());
+    /// let mut profiler = Profiler::new(interp);
+    /// profiler.run().unwrap();
+    /// assert_eq!(profiler.listing(), "  | Count down\n3 | +++\n7 | [-]\n");
+    /// ```
+    pub fn listing(&self) -> String {
+        let program = self.program();
+        let lines: Vec<&str> = program.source().lines().collect();
+        let mut totals = vec![None; lines.len()];
+        for (pc, count) in self.counts.iter().enumerate() {
+            let line = program.position(pc).expect("in").line - 1;
+            *totals[line].get_or_insert(0) += count;
+        }
+        let width = totals.iter().flatten().map(|c| c.to_string().len()).max().unwrap_or(0);
+        let mut out = String::new();
+        for (line, total) in lines.iter().zip(totals) {
+            let count = total.map_or(String::new(), |c| c.to_string());
+            let row = format!("{:>w$} | {}", count, line, w = width);
+            writeln!(out, "{}", row.trim_end()).expect("writing to a String");
+        }
+        out
+    }
+
+    /// Render a table of the `n` loops with the most cycles, the most first.
+    ///
+    /// ```
+    /// use brainfuck::Interpreter;
+    /// use brainfuck::program::Program;
+    /// use brainfuck::profiler::Profiler;
+    /// use brainfuck::tape::VecTape;
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// interp.load(Program::parse("++[>+++[-]<-]").unwrap());
+    /// let mut profiler = Profiler::new(interp);
+    /// profiler.run().unwrap();
+    /// assert_eq!(profiler.top(5), "\
+    /// cycles      % iterations  entries  location  loop
+    ///     29  93.5%          2        1  1:3       [>+++[-]<-]
+    ///     14  45.2%          6        2  1:8       [-]
+    /// ");
+    /// ```
+    pub fn top(&self, n: usize) -> String {
+        let program = self.program();
+        let mut loops = self.loops();
+        loops.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.start.cmp(&b.start)));
+        loops.truncate(n);
+        let total = self.cycles().max(1) as f64;
+        let rows: Vec<(Loop, String, String)> = loops.into_iter().map(|l| {
+            let text: String = (l.start..=l.end).map(|pc| program.get(pc).expect("in").to_string()).collect();
+            (l, program.location(l.start).expect("in"), shorten(&text, 40))
+        }).collect();
+        let width = rows.iter().map(|r| r.1.len()).fold("location".len(), usize::max);
+        let mut out = String::new();
+        writeln!(out, "cycles      % iterations  entries  {:w$}  loop", "location", w = width)
+            .expect("writing to a String");
+        for (l, location, text) in rows {
+            writeln!(out, "{:>6} {:>5.1}% {:>10} {:>8}  {:w$}  {}",
+                     l.cycles, 100.0 * l.cycles as f64 / total, l.iterations, l.entries,
+                     location, text, w = width)
+                .expect("writing to a String");
+        }
+        out
+    }
+
+    /// Write the profile as a JSON object, on one line. It holds the
+    /// `schema`, the name of the `program` or `null`, the total `cycles`,
+    /// the `instructions` with the fields of `Count`, and the `loops` with
+    /// the fields of `Loop`. Positions are split into `line` and `column`.
+    ///
+    /// ```
+    /// use brainfuck::Interpreter;
+    /// use brainfuck::program::Program;
+    /// use brainfuck::profiler::Profiler;
+    /// use brainfuck::tape::VecTape;
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// interp.load(Program::parse("+[-]").unwrap());
+    /// let mut profiler = Profiler::new(interp);
+    /// profiler.run().unwrap();
+    /// let mut json = Vec::new();
+    /// profiler.write_json(&mut json).unwrap();
+    /// assert!(String::from_utf8(json).unwrap().starts_with(
+    ///     r#"{"schema":1,"program":null,"cycles":4,"instructions":[{"pc":0,"#));
+    /// ```
+    pub fn write_json<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        let name = self.program().name().map_or("null".into(), json_string);
+        write!(out, r#"{{"schema":{},"program":{},"cycles":{},"instructions":["#,
+               SCHEMA, name, self.cycles())?;
+        for (i, c) in self.counts().iter().enumerate() {
+            write!(out, r#"{}{{"pc":{},"instruction":{},"line":{},"column":{},"count":{}}}"#,
+                   if i == 0 { "" } else { "," },
+                   c.pc, json_string(&c.instruction.to_string()), c.position.line, c.position.column,
+                   c.count)?;
+        }
+        write!(out, r#"],"loops":["#)?;
+        for (i, l) in self.loops().iter().enumerate() {
+            write!(out, r#"{}{{"start":{},"end":{},"line":{},"column":{},"entries":{},"iterations":{},"cycles":{}}}"#,
+                   if i == 0 { "" } else { "," },
+                   l.start, l.end, l.position.line, l.position.column, l.entries, l.iterations, l.cycles)?;
+        }
+        writeln!(out, "]}}")
+    }
+
+    /// Write the profile as CSV, with a header and a row for the program,
+    /// each instruction and each loop, told apart by the `kind` column.
+    /// Columns which don't apply to a row are empty.
+    ///
+    /// ```
+    /// use brainfuck::Interpreter;
+    /// use brainfuck::program::Program;
+    /// use brainfuck::profiler::Profiler;
+    /// use brainfuck::tape::VecTape;
+    ///
+    /// let mut interp = Interpreter::<VecTape>::default();
+    /// let mut program = Program::parse("+[-]").unwrap();
+    /// program.set_name("clear.b");
+    /// interp.load(program);
+    /// let mut profiler = Profiler::new(interp);
+    /// profiler.run().unwrap();
+    /// let mut csv = Vec::new();
+    /// profiler.write_csv(&mut csv).unwrap();
+    /// assert_eq!(String::from_utf8(csv).unwrap(), "\
+    /// schema,program,kind,pc,end,instruction,line,column,count,entries,iterations,cycles
+    /// 1,clear.b,program,,,,,,,,,4
+    /// 1,clear.b,instruction,0,,+,1,1,1,,,
+    /// 1,clear.b,instruction,1,,[,1,2,1,,,
+    /// 1,clear.b,instruction,2,,-,1,3,1,,,
+    /// 1,clear.b,instruction,3,,],1,4,1,,,
+    /// 1,clear.b,loop,1,3,,1,2,,1,1,3
+    /// ");
+    /// ```
+    pub fn write_csv<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        let name = csv_field(self.program().name().unwrap_or(""));
+        writeln!(out, "schema,program,kind,pc,end,instruction,line,column,count,entries,iterations,cycles")?;
+        writeln!(out, "{},{},program,,,,,,,,,{}", SCHEMA, name, self.cycles())?;
+        for c in self.counts() {
+            writeln!(out, "{},{},instruction,{},,{},{},{},{},,,",
+                     SCHEMA, name, c.pc, csv_field(&c.instruction.to_string()),
+                     c.position.line, c.position.column, c.count)?;
+        }
+        for l in self.loops() {
+            writeln!(out, "{},{},loop,{},{},,{},{},,{},{},{}",
+                     SCHEMA, name, l.start, l.end, l.position.line, l.position.column,
+                     l.entries, l.iterations, l.cycles)?;
+        }
+        Ok(())
+    }
+
+    /// The program being profiled.
+    fn program(&self) -> &Program {
+        self.interp.program().expect("loaded")
+    }
+}
+
+/// Quote text as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).expect("writing to a String"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quote text as a CSV field, when it has a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Cut text down to at most `max` characters, ending it with `...` when
+/// it's cut.
+fn shorten(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        text.to_owned()
+    } else {
+        format!("{}...", &text[..max - 3])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tape::VecTape;
+    use super::*;
+
+    fn profile(source: &str) -> Profiler<'static, VecTape> {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse(source).unwrap());
+        let mut profiler = Profiler::new(interp);
+        profiler.run().unwrap();
+        profiler
+    }
+
+    #[test]
+    fn counts() {
+        let profiler = profile("+\n+[-]");
+        let counts: Vec<(u64, Position)> = profiler.counts().iter().map(|c| (c.count, c.position)).collect();
+        assert_eq!(counts, vec![(1, Position { line: 1, column: 1 }),
+                                (1, Position { line: 2, column: 1 }),
+                                (1, Position { line: 2, column: 2 }),
+                                (2, Position { line: 2, column: 3 }),
+                                (2, Position { line: 2, column: 4 })]);
+        assert_eq!(profiler.cycles(), 7);
+        assert_eq!(profiler.cycles(), profiler.interpreter().cycles());
+    }
+
+    #[test]
+    fn loops() {
+        let profiler = profile("[never]++[>+++[-]<-]");
+        let loops: Vec<(usize, usize, u64, u64, u64)> = profiler.loops().iter()
+            .map(|l| (l.start, l.end, l.entries, l.iterations, l.cycles))
+            .collect();
+        assert_eq!(loops, vec![(0, 1, 0, 0, 1), (4, 14, 1, 2, 29), (9, 11, 2, 6, 14)]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut interp = Interpreter::<VecTape>::default();
+        interp.load(Program::parse("++[>+<-]<").unwrap());
+        let mut profiler = Profiler::new(interp);
+        assert!(profiler.run().is_err());
+        assert_eq!(profiler.loops()[0].iterations, 2);
+        assert_eq!(profiler.counts()[8].count, 0);
+    }
+
+    #[test]
+    fn listing() {
+        assert_eq!(profile("\n\n[-]").listing(), "  |\n  |\n1 | [-]\n");
+    }
+
+    #[test]
+    fn top() {
+        let profiler = profile("+[-]+[-]++++[>++++++++++++++++++++++++++++++++++++++++++<-]");
+        let top = profiler.top(1);
+        assert_eq!(top.lines().count(), 2);
+        let row = top.lines().nth(1).unwrap();
+        assert!(row.starts_with("   185  93.9%          4        1  1:13      [>+++"), "{}", top);
+        assert!(row.ends_with("+++..."), "{}", top);
+        assert_eq!(row.len(), 45 + 40);
+        assert_eq!(profile("+").top(3), "cycles      % iterations  entries  location  loop\n");
+    }
+
+    #[test]
+    fn json() {
+        let mut interp = Interpreter::<VecTape>::default();
+        let mut program = Program::parse(",[.,]").unwrap();
+        program.set_name("echo \"1\".b");
+        interp.load(program);
+        let mut profiler = Profiler::new(interp);
+        profiler.run().unwrap();
+        let mut json = Vec::new();
+        profiler.write_json(&mut json).unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), concat!(
+            r#"{"schema":1,"program":"echo \"1\".b","cycles":2,"instructions":["#,
+            r#"{"pc":0,"instruction":",","line":1,"column":1,"count":1},"#,
+            r#"{"pc":1,"instruction":"[","line":1,"column":2,"count":1},"#,
+            r#"{"pc":2,"instruction":".","line":1,"column":3,"count":0},"#,
+            r#"{"pc":3,"instruction":",","line":1,"column":4,"count":0},"#,
+            r#"{"pc":4,"instruction":"]","line":1,"column":5,"count":0}],"#,
+            r#""loops":[{"start":1,"end":4,"line":1,"column":2,"entries":0,"iterations":0,"cycles":1}]}"#,
+            "\n"));
+        assert_eq!(json_string("a\\b\n"), r#""a\\b\u000a""#);
+    }
+
+    #[test]
+    fn csv() {
+        let profiler = profile(",");
+        let mut csv = Vec::new();
+        profiler.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(2), Some(r#"1,,instruction,0,,",",1,1,1,,,"#));
+        assert_eq!(csv_field("a \"b\""), r#""a ""b""""#);
+    }
+}
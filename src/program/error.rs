@@ -1,19 +1,101 @@
 use std::{fmt, io};
+use super::Position;
 
-/// The error type for dealing with tapes.
+/// The error type for dealing with programs.
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
-    MissingOpenBracket(usize),
-    MissingCloseBracket(usize),
+    /// The source has brackets without a match, all of them are listed in
+    /// the order they appear.
+    Brackets(Vec<Mismatch>),
+}
+
+/// A bracket without a match.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    /// A `]` at the given position, with no `[` before it.
+    MissingOpenBracket(Position),
+    /// A `[` at the given position, with no `]` after it.
+    MissingCloseBracket(Position),
+}
+
+impl Mismatch {
+    /// The position of the unmatched bracket.
+    pub fn position(&self) -> Position {
+        match *self {
+            Mismatch::MissingOpenBracket(p) | Mismatch::MissingCloseBracket(p) => p,
+        }
+    }
+}
+
+impl Error {
+    /// Render this error as a diagnostic for the given source, pointing out
+    /// each unmatched bracket with a caret under it. The `name` of the
+    /// source, usually a path, is given with each location.
+    ///
+    /// ```
+    /// use brainfuck::program::Program;
+    ///
+    /// let source = "+[\n>]]";
+    /// let error = Program::parse(source).unwrap_err();
+    /// assert_eq!(error.render("example.b", source), "\
+    /// error: Missing opening bracket for `]`
+    ///  --> example.b:2:3
+    ///   |
+    /// 2 | >]]
+    ///   |   ^
+    /// ");
+    /// ```
+    pub fn render(&self, name: &str, source: &str) -> String {
+        let mismatches = match *self {
+            Error::Brackets(ref m) => m,
+            _ => return format!("error: {}\n", self),
+        };
+        let rendered: Vec<String> = mismatches.iter()
+            .map(|m| diagnostic("error", m, m.position(), name, source))
+            .collect();
+        rendered.join("\n")
+    }
+}
+
+/// Render a diagnostic of the given level, like `error`, pointing out the
+/// given position in the source with a caret under it.
+pub(super) fn diagnostic<D: fmt::Display>(level: &str, message: D, position: Position,
+                                          name: &str, source: &str) -> String {
+    let line = source.lines().nth(position.line - 1).unwrap_or("");
+    let gutter = position.line.to_string().len();
+    // Keep tabs in the caret's indent, so it lines up.
+    let indent: String = line.chars()
+        .take(position.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let mut rendered = format!("{}: {}\n", level, message);
+    rendered.push_str(&format!("{:w$}--> {}:{}\n", "", name, position, w = gutter));
+    rendered.push_str(&format!("{:w$} |\n", "", w = gutter));
+    rendered.push_str(&format!("{} | {}\n", position.line, line));
+    rendered.push_str(&format!("{:w$} | {}^\n", "", indent, w = gutter));
+    rendered
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mismatch::MissingOpenBracket(_) => write!(f, "Missing opening bracket for `]`"),
+            Mismatch::MissingCloseBracket(_) => write!(f, "Missing closing bracket for `[`"),
+        }
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::Io(ref e) => e.fmt(f),
-            Error::MissingOpenBracket(n) => write!(f, "Missing opening bracket at pc={}", n),
-            Error::MissingCloseBracket(n) => write!(f, "{} missing closing brackets", n),
+            Error::Brackets(ref mismatches) => {
+                let list: Vec<String> = mismatches.iter()
+                    .map(|m| format!("{} at {}", m, m.position()))
+                    .collect();
+                write!(f, "{}", list.join(", "))
+            },
         }
     }
 }
@@ -0,0 +1,122 @@
+use std::fmt;
+use crate::Instruction;
+use super::{Position, Span};
+use super::error::diagnostic;
+
+/// A likely mistake in a program, found by `Program::lint`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// An instruction at the given position which the next one undoes, like
+    /// `+-` or `<>`.
+    Cancelled(Instruction, Position),
+    /// A loop at the given position which never runs, since the cell is
+    /// always `0` there. This is at the start of the program, or right
+    /// after another loop.
+    DeadLoop(Position),
+    /// A loop at the given position with nothing in it, which never ends
+    /// once it's entered.
+    EmptyLoop(Position),
+}
+
+impl Warning {
+    /// The position of the instruction warned about.
+    pub fn position(&self) -> Position {
+        match *self {
+            Warning::Cancelled(_, p) | Warning::DeadLoop(p) | Warning::EmptyLoop(p) => p,
+        }
+    }
+
+    /// Render this warning as a diagnostic for the given source, like
+    /// `Error::render`.
+    ///
+    /// ```
+    /// use brainfuck::program::Program;
+    ///
+    /// let source = "+>\n<.";
+    /// let program = Program::parse(source).unwrap();
+    /// assert_eq!(program.lint()[0].render("example.b", source), "\
+    /// warning: `>` is undone by the instruction after it
+    ///  --> example.b:1:2
+    ///   |
+    /// 1 | +>
+    ///   |  ^
+    /// ");
+    /// ```
+    pub fn render(&self, name: &str, source: &str) -> String {
+        diagnostic("warning", self, self.position(), name, source)
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::Cancelled(i, _) => write!(f, "`{}` is undone by the instruction after it", i),
+            Warning::DeadLoop(_) => write!(f, "Loop never runs, the cell is always 0 here"),
+            Warning::EmptyLoop(_) => write!(f, "Empty loop never ends once entered"),
+        }
+    }
+}
+
+/// Find the warnings for a program, in the order they appear.
+pub(super) fn lint(asl: &[Instruction], spans: &[Span]) -> Vec<Warning> {
+    use crate::Instruction::*;
+
+    let mut warnings = Vec::new();
+    for (pc, &instruction) in asl.iter().enumerate() {
+        let position = spans[pc].position;
+        let previous = pc.checked_sub(1).map(|p| asl[p]);
+        match (instruction, asl.get(pc + 1)) {
+            (IncVal, Some(DecVal)) | (DecVal, Some(IncVal)) |
+            (IncPtr, Some(DecPtr)) | (DecPtr, Some(IncPtr)) => {
+                warnings.push(Warning::Cancelled(instruction, position));
+            },
+            (SkipForward(close), _) => {
+                if let None | Some(SkipBackward(_)) = previous {
+                    warnings.push(Warning::DeadLoop(position));
+                } else if close == pc + 1 {
+                    warnings.push(Warning::EmptyLoop(position));
+                }
+            },
+            _ => {},
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program::Program;
+    use super::*;
+
+    fn lint(source: &str) -> Vec<Warning> {
+        Program::parse(source).unwrap().lint()
+    }
+
+    fn at(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(lint("++[>+<-]>."), vec![]);
+        assert_eq!(lint("fixtures are fine"), vec![]);
+    }
+
+    #[test]
+    fn cancelled() {
+        assert_eq!(lint("+-+"), vec![Warning::Cancelled(Instruction::IncVal, at(1, 1)),
+                                     Warning::Cancelled(Instruction::DecVal, at(1, 2))]);
+        assert_eq!(lint("+\n><"), vec![Warning::Cancelled(Instruction::IncPtr, at(2, 1))]);
+    }
+
+    #[test]
+    fn dead_loops() {
+        assert_eq!(lint("[comment]+[-][>]"), vec![Warning::DeadLoop(at(1, 1)),
+                                                  Warning::DeadLoop(at(1, 14))]);
+    }
+
+    #[test]
+    fn empty_loops() {
+        assert_eq!(lint("+[]"), vec![Warning::EmptyLoop(at(1, 2))]);
+    }
+}
@@ -5,7 +5,10 @@ use std::fs::File;
 use super::Instruction;
 
 // Re-exports.
-pub use self::error::Error;
+pub use self::error::{Error, Mismatch};
+pub use self::lint::Warning;
+pub use self::position::Position;
+pub use self::span::Span;
 
 /// The logic desired to be run by the brainfuck interpreter.
 ///
@@ -13,18 +16,35 @@ pub use self::error::Error;
 /// brainfuck source text. The main operations of a program is creating
 /// one with the `parse` function, and getting the instruction for a
 /// given program counter with the `get` function.
+///
+/// The source text is kept along with a [`Span`][span] for each
+/// instruction, so an instruction can be traced back to where it was
+/// written, with the `span`, `location` and `comment` functions.
+///
+/// [span]: struct.Span.html
 #[derive(Debug)]
 pub struct Program {
     asl: Vec<Instruction>,
+    spans: Vec<Span>,
+    source: String,
+    name: Option<String>,
 }
 
 impl Program {
     /// Create a program from source text.
+    ///
+    /// When the source has unmatched brackets, the error lists every one of
+    /// them, with its position.
     pub fn parse(source: &str) -> Result<Program, Error> {
         let mut asl = Vec::new();
-        let mut count = 0usize;
+        let mut spans = Vec::new();
+        let mut mismatches = Vec::new();
         let mut stack = Vec::new();
-        for c in source.chars() {
+        let mut position = Position::start();
+        for (offset, c) in source.char_indices() {
+            let here = position;
+            position = position.next(c);
+            let count = asl.len();
             let instruction = match c {
                 '>' => Instruction::IncPtr,
                 '<' => Instruction::DecPtr,
@@ -42,7 +62,11 @@ impl Program {
                 ']' => {
                     let open_pc = match stack.pop() {
                         Some(o) => o,
-                        None => return Err(Error::MissingOpenBracket(count))
+                        None => {
+                            // Keep going, to find every unmatched bracket.
+                            mismatches.push(Mismatch::MissingOpenBracket(here));
+                            continue
+                        },
                     };
                     let open = asl.get_mut(open_pc).expect("in");
                     *open = Instruction::SkipForward(count);
@@ -50,28 +74,127 @@ impl Program {
                 },
                 _ => continue,
             };
-            count += 1;
             asl.push(instruction);
+            spans.push(Span {
+                start: offset,
+                end: offset + c.len_utf8(),
+                position: here,
+            });
+        }
+        for open_pc in stack {
+            mismatches.push(Mismatch::MissingCloseBracket(spans[open_pc].position));
         }
-        if !stack.is_empty() {
-            return Err(Error::MissingCloseBracket(stack.len()))
+        if !mismatches.is_empty() {
+            mismatches.sort_by_key(Mismatch::position);
+            return Err(Error::Brackets(mismatches))
         }
         Ok(Program {
-            asl: asl
+            asl,
+            spans,
+            source: source.to_owned(),
+            name: None,
         })
     }
 
     /// Get the instruction at the given program counter.
     pub fn get(&self, iptr: usize) -> Option<Instruction> {
-        self.asl.get(iptr).map(|i| *i)
+        self.asl.get(iptr).copied()
+    }
+
+    /// Get the position in the source of the instruction at the given
+    /// program counter.
+    pub fn position(&self, iptr: usize) -> Option<Position> {
+        self.span(iptr).map(|s| s.position)
+    }
+
+    /// Get the span in the source of the instruction at the given program
+    /// counter.
+    pub fn span(&self, iptr: usize) -> Option<Span> {
+        self.spans.get(iptr).copied()
+    }
+
+    /// Get the program counter of the first instruction at or after the
+    /// given position in the source.
+    pub fn pc_at(&self, position: Position) -> Option<usize> {
+        let pc = self.spans.partition_point(|s| s.position < position);
+        if pc < self.spans.len() { Some(pc) } else { None }
+    }
+
+    /// Get the location of the instruction at the given program counter, as
+    /// `name:line:column`, or `line:column` for a program without a name.
+    pub fn location(&self, iptr: usize) -> Option<String> {
+        let position = self.position(iptr)?;
+        Some(match self.name {
+            Some(ref name) => format!("{}:{}", name, position),
+            None => position.to_string(),
+        })
+    }
+
+    /// Get the comment documenting the instruction at the given program
+    /// counter. This is the text on its line, or the nearest line above
+    /// with any, without the commands.
+    ///
+    /// ```
+    /// use brainfuck::program::Program;
+    ///
+    /// let program = Program::parse("Clear the cell\n[-]").unwrap();
+    /// assert_eq!(program.comment(1).unwrap(), "Clear the cell");
+    /// ```
+    pub fn comment(&self, iptr: usize) -> Option<String> {
+        span::comment(&self.source, self.position(iptr)?.line)
+    }
+
+    /// The source text the program was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The name of the program's source, like the path of its file.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Set the name of the program's source, used in locations.
+    pub fn set_name<S: Into<String>>(&mut self, name: S) {
+        self.name = Some(name.into());
+    }
+
+    /// The number of instructions in the program.
+    pub fn len(&self) -> usize {
+        self.asl.len()
+    }
+
+    /// Returns true when the program has no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.asl.is_empty()
+    }
+
+    /// Look for likely mistakes in the program, like loops which can never
+    /// run. A program with warnings still runs as written.
+    ///
+    /// ```
+    /// use brainfuck::program::{Position, Program, Warning};
+    ///
+    /// let program = Program::parse("[-]+[]").unwrap();
+    /// assert_eq!(program.lint(), vec![
+    ///     Warning::DeadLoop(Position { line: 1, column: 1 }),
+    ///     Warning::EmptyLoop(Position { line: 1, column: 5 }),
+    /// ]);
+    /// ```
+    pub fn lint(&self) -> Vec<Warning> {
+        lint::lint(&self.asl, &self.spans)
     }
 
     /// Create a program from a file.
+    ///
+    /// The program is named after the path.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Program, Error> {
-        let mut file = File::open(path)?;
+        let mut file = File::open(&path)?;
         let mut source = String::new();
         file.read_to_string(&mut source)?;
-        Program::parse(&source)
+        let mut program = Program::parse(&source)?;
+        program.set_name(path.as_ref().display().to_string());
+        Ok(program)
     }
 }
 
@@ -88,6 +211,15 @@ impl fmt::Display for Program {
 /// Program errors.
 mod error;
 
+/// Likely mistakes in programs.
+mod lint;
+
+/// Locations in source text.
+mod position;
+
+/// Tracing instructions back to source text.
+mod span;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,4 +247,64 @@ mod tests {
         let program = Program::parse("[[]]]");
         assert!(program.is_err());
     }
+
+    #[test]
+    fn positions() {
+        let program = Program::parse("+ comment\n  >[-]").unwrap();
+        assert_eq!(program.position(0), Some(Position { line: 1, column: 1 }));
+        assert_eq!(program.position(1), Some(Position { line: 2, column: 3 }));
+        assert_eq!(program.position(4), Some(Position { line: 2, column: 6 }));
+        assert_eq!(program.position(5), None);
+    }
+
+    #[test]
+    fn spans() {
+        let program = Program::parse("é+\n-").unwrap();
+        let span = program.span(0).unwrap();
+        assert_eq!((span.start, span.end), (2, 3));
+        assert_eq!(&program.source()[span.start..span.end], "+");
+        assert_eq!(program.span(1).unwrap().position, Position { line: 2, column: 1 });
+    }
+
+    #[test]
+    fn pc_at() {
+        let program = Program::parse("+ add\n\n>>").unwrap();
+        assert_eq!(program.pc_at(Position { line: 1, column: 1 }), Some(0));
+        assert_eq!(program.pc_at(Position { line: 1, column: 2 }), Some(1));
+        assert_eq!(program.pc_at(Position { line: 2, column: 1 }), Some(1));
+        assert_eq!(program.pc_at(Position { line: 3, column: 2 }), Some(2));
+        assert_eq!(program.pc_at(Position { line: 3, column: 3 }), None);
+    }
+
+    #[test]
+    fn location() {
+        let mut program = Program::parse("\n  +").unwrap();
+        assert_eq!(program.location(0).unwrap(), "2:3");
+        program.set_name("add.b");
+        assert_eq!(program.location(0).unwrap(), "add.b:2:3");
+        assert_eq!(program.location(1), None);
+        let program = Program::from_file("fixtures/helloworld.b").unwrap();
+        assert_eq!(program.name(), Some("fixtures/helloworld.b"));
+    }
+
+    #[test]
+    fn all_mismatches() {
+        let mismatches = match Program::parse("]+[\n[]\n[") {
+            Err(Error::Brackets(m)) => m,
+            r => panic!("expected Error::Brackets, got {:?}", r),
+        };
+        assert_eq!(mismatches, vec![
+            Mismatch::MissingOpenBracket(Position { line: 1, column: 1 }),
+            Mismatch::MissingCloseBracket(Position { line: 1, column: 3 }),
+            Mismatch::MissingCloseBracket(Position { line: 3, column: 1 }),
+        ]);
+    }
+
+    #[test]
+    fn render() {
+        let source = "++\n\t[>\n";
+        let error = Program::parse(source).unwrap_err();
+        assert_eq!(error.render("x.b", source),
+                   "error: Missing closing bracket for `[`\n --> x.b:2:2\n  |\n2 | \t[>\n  | \t^\n");
+    }
 }
@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// A location in the source text of a program.
+///
+/// Both the line and column start at `1`, and columns count characters, not
+/// bytes. This displays as `line:column`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// The line, starting at `1`.
+    pub line: usize,
+    /// The column within the line, starting at `1`.
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of the first character of a source.
+    pub fn start() -> Position {
+        Position { line: 1, column: 1 }
+    }
+
+    /// The position of the character after the given one, at this position.
+    pub fn next(self, c: char) -> Position {
+        if c == '\n' {
+            Position { line: self.line + 1, column: 1 }
+        } else {
+            Position { line: self.line, column: self.column + 1 }
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next() {
+        let p = Position::start().next('+').next('+');
+        assert_eq!(p, Position { line: 1, column: 3 });
+        assert_eq!(p.next('\n'), Position { line: 2, column: 1 });
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Position { line: 12, column: 4 }.to_string(), "12:4");
+    }
+}
@@ -0,0 +1,55 @@
+use super::Position;
+
+/// Where an instruction came from in the source text of a program.
+///
+/// The `start` and `end` are byte offsets into the source, so
+/// `&source[span.start..span.end]` is the text of the instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// The byte offset of the start of the instruction.
+    pub start: usize,
+    /// The byte offset just past the end of the instruction.
+    pub end: usize,
+    /// The line and column of the start of the instruction.
+    pub position: Position,
+}
+
+/// The comment nearest to the given line of the source, looking at the line
+/// itself and then each line above it.
+///
+/// A line's comment is what's left after removing every command, with
+/// whitespace collapsed. Lines left with no letters or digits, like
+/// separators of `=`, don't count.
+pub(super) fn comment(source: &str, line: usize) -> Option<String> {
+    let lines: Vec<&str> = source.lines().take(line).collect();
+    lines.iter().rev()
+        .map(|l| {
+            let text: String = l.chars()
+                .map(|c| if "<>+-.,[]".contains(c) { ' ' } else { c })
+                .collect();
+            text.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+        .find(|text| text.chars().any(char::is_alphanumeric))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_line() {
+        let source = "+++ three\n>>";
+        assert_eq!(comment(source, 1), Some("three".into()));
+    }
+
+    #[test]
+    fn line_above() {
+        let source = "set up   cells\n=====\n>>+<<\n";
+        assert_eq!(comment(source, 3), Some("set up cells".into()));
+    }
+
+    #[test]
+    fn none() {
+        assert_eq!(comment("++\n==\n>>", 3), None);
+    }
+}
@@ -0,0 +1,91 @@
+use std::str::FromStr;
+use crate::tape::{PointerPolicy, ValuePolicy};
+
+/// A line typed into the REPL, either brainfuck to run, or a command like
+/// `:load fixtures/fib.b`.
+///
+/// Commands start with a `:`, and each has a one letter short form, given
+/// in the `HELP` text. Every other line is brainfuck.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Run the given source on the tape.
+    Run(String),
+    /// Show the cells within the given distance of the pointer.
+    Tape(usize),
+    /// Set every cell to `0`, and move the pointer back to the start.
+    Reset,
+    /// Run the program in the file at the given path on the tape.
+    Load(String),
+    /// Use the given policy for values going out of range.
+    Values(ValuePolicy),
+    /// Use the given policy for the pointer going off the tape.
+    Pointer(PointerPolicy),
+    /// Show the `HELP` text.
+    Help,
+    /// Stop the REPL.
+    Quit,
+}
+
+/// The commands the REPL understands.
+pub const HELP: &str = "\
+<brainfuck>                 Run the brainfuck on the tape.
+:tape [radius]        :t    Show the cells around the pointer, 8 by default.
+:reset                :r    Clear the tape, with the pointer at the start.
+:load <path>          :l    Run the program in a file on the tape.
+:values <policy>      :v    Make values wrap, saturate or error.
+:pointer <policy>     :p    Make the pointer wrap, clamp, error or grow.
+:help                 :h    Show this help.
+:quit                 :q    Stop the REPL.
+";
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Command, String> {
+        let line = s.trim();
+        if !line.starts_with(':') {
+            return Ok(Command::Run(s.to_owned()))
+        }
+        let (name, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        let command = match (name, rest) {
+            (":tape" | ":t", "") => Command::Tape(8),
+            (":tape" | ":t", r) => Command::Tape(r.parse().map_err(|_| format!("Invalid number `{}`", r))?),
+            (":reset" | ":r", "") => Command::Reset,
+            (":load" | ":l", path) if !path.is_empty() => Command::Load(path.to_owned()),
+            (":values" | ":v", policy) => Command::Values(policy.parse()?),
+            (":pointer" | ":p", policy) => Command::Pointer(policy.parse()?),
+            (":help" | ":h", "") => Command::Help,
+            (":quit" | ":q", "") => Command::Quit,
+            _ => return Err(format!("Unknown command `{}`, try `:help`", line)),
+        };
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        assert_eq!("+[->+<]\n".parse(), Ok(Command::Run("+[->+<]\n".into())));
+        assert_eq!(":t".parse(), Ok(Command::Tape(8)));
+        assert_eq!(" :tape 2 ".parse(), Ok(Command::Tape(2)));
+        assert_eq!(":load my programs/a.b".parse(), Ok(Command::Load("my programs/a.b".into())));
+        assert_eq!(":v saturate".parse(), Ok(Command::Values(ValuePolicy::Saturate)));
+        assert_eq!(":pointer grow".parse(), Ok(Command::Pointer(PointerPolicy::Grow)));
+        assert_eq!(":q".parse(), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn bad_commands() {
+        assert!(":".parse::<Command>().is_err());
+        assert!(":load".parse::<Command>().is_err());
+        assert!(":tape x".parse::<Command>().is_err());
+        assert!(":values grow".parse::<Command>().is_err());
+        assert!(":reset now".parse::<Command>().is_err());
+    }
+}
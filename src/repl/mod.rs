@@ -0,0 +1,186 @@
+use std::fs;
+use std::io::{self, Write};
+use crate::{CYCLE_LIMIT, Interpreter};
+use crate::debugger::window;
+use crate::program::Program;
+use crate::tape::{Integer, PolicyTape};
+
+// Re-exports.
+pub use self::command::{Command, HELP};
+
+/// An interactive session, running each line of brainfuck on the same tape.
+///
+/// The REPL executes [`Command`][command]s, writing what it has to say to a
+/// given writer, which is kept apart from the writer of the programs it
+/// runs. Like the `Debugger`, reading lines is left to the caller.
+///
+/// The tape is a `PolicyTape`, so the behavior of values and the pointer
+/// can be changed between lines, without losing the cells.
+///
+/// ```
+/// use brainfuck::Interpreter;
+/// use brainfuck::repl::Repl;
+/// use brainfuck::tape::PolicyTape;
+///
+/// let mut repl = Repl::new(Interpreter::<PolicyTape>::default());
+/// let mut out = Vec::new();
+/// for line in &["+++", ">++", "<[->+<]", ":values saturate", "-", ":tape 1"] {
+///     repl.execute(line.parse().unwrap(), &mut out).unwrap();
+/// }
+/// assert_eq!(String::from_utf8(out).unwrap(), "[0:0] 1:5\n");
+/// ```
+///
+/// [command]: enum.Command.html
+pub struct Repl<'a, C: Integer = u8> {
+    interp: Interpreter<'a, PolicyTape<C>>,
+    cycle_limit: Option<u64>,
+}
+
+impl<'a, C: Integer> Repl<'a, C> {
+    /// Create a REPL running on the given interpreter's tape.
+    pub fn new(interp: Interpreter<'a, PolicyTape<C>>) -> Repl<'a, C> {
+        Repl {
+            interp,
+            cycle_limit: Some(CYCLE_LIMIT),
+        }
+    }
+
+    /// Stop each program once it has executed the given number of cycles,
+    /// or never when `None`. This is `CYCLE_LIMIT` by default.
+    pub fn cycle_limit(&mut self, limit: Option<u64>) -> &mut Self {
+        self.cycle_limit = limit;
+        self
+    }
+
+    /// The interpreter running the programs.
+    pub fn interpreter(&self) -> &Interpreter<'a, PolicyTape<C>> {
+        &self.interp
+    }
+
+    /// Execute a command, writing the result to `out`. Returns false once
+    /// the command is `Quit`.
+    pub fn execute<W: Write>(&mut self, command: Command, out: &mut W) -> io::Result<bool> {
+        match command {
+            Command::Run(source) => self.run("<line>", &source, out)?,
+            Command::Load(path) => match fs::read_to_string(&path) {
+                Ok(source) => self.run(&path, &source, out)?,
+                Err(e) => writeln!(out, "Error: {}: {}", path, e)?,
+            },
+            Command::Tape(radius) => writeln!(out, "{}", window(self.interp.tape(), radius))?,
+            Command::Reset => self.interp.tape_mut().clear(),
+            Command::Values(policy) => {
+                self.interp.tape_mut().values(policy);
+            },
+            Command::Pointer(policy) => {
+                self.interp.tape_mut().pointer(policy);
+            },
+            Command::Help => write!(out, "{}", HELP)?,
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Parse the source and run it on the tape, writing any error to `out`.
+    fn run<W: Write>(&mut self, name: &str, source: &str, out: &mut W) -> io::Result<()> {
+        let mut program = match Program::parse(source) {
+            Ok(program) => program,
+            Err(e) => return write!(out, "{}", e.render(name, source)),
+        };
+        program.set_name(name);
+        // The limit is counted from the start of the program.
+        let limit = self.cycle_limit.map(|n| self.interp.cycles() + n);
+        if let Err(e) = self.interp.load(program).cycle_limit(limit).run() {
+            writeln!(out, "Error: {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parsing REPL commands.
+mod command;
+
+#[cfg(test)]
+mod tests {
+    use crate::tape::Tape;
+    use super::*;
+
+    fn session(lines: &[&str]) -> String {
+        let mut repl = Repl::new(Interpreter::<PolicyTape>::default());
+        let mut out = Vec::new();
+        for line in lines {
+            repl.execute(line.parse().unwrap(), &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn keeps_tape() {
+        assert_eq!(session(&["++>+", "<[->+<]>", ":t 1"]), "0:0 [1:3]\n");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(session(&["+[", "<", "+", ":t 0"]), "\
+error: Missing closing bracket for `[`
+ --> <line>:1:2
+  |
+1 | +[
+  |  ^
+Error: Tape pointer underflowed at 0 (<line>:1:1) `<`, after 0 cycles with the pointer at 0
+[0:1]
+");
+    }
+
+    #[test]
+    fn cycle_limit() {
+        let mut repl = Repl::new(Interpreter::<PolicyTape>::default());
+        repl.cycle_limit(Some(4));
+        let mut out = Vec::new();
+        for line in &["+++", "+++", "+++++"] {
+            repl.execute(line.parse().unwrap(), &mut out).unwrap();
+        }
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "Error: Cycle limit hit\n");
+        assert_eq!(repl.interpreter().tape().get(0), Some(10));
+    }
+
+    #[test]
+    fn reset() {
+        assert_eq!(session(&["+>++", ":reset", ">", ":t 1"]), "0:0 [1:0]\n");
+    }
+
+    #[test]
+    fn huge_window() {
+        assert_eq!(session(&[":p grow", "+<+>>", ":t 10000000000"]), "0:1 1:1 [2:0]\n");
+    }
+
+    #[test]
+    fn load() {
+        let out = session(&[":load fixtures/helloworld.b", ":l fixtures/missing.b"]);
+        assert!(out.starts_with("Error: fixtures/missing.b: "), "{}", out);
+    }
+
+    #[test]
+    fn policies() {
+        assert_eq!(session(&["-"]), "Error: Tape value underflowed at 0 (<line>:1:1) `-`, \
+                                     after 0 cycles with the pointer at 0\n");
+        assert_eq!(session(&[":v wrap", "-", ":p clamp", "<<", ":v error", "+", ":t 1"]),
+                   "Error: Tape value overflowed at 0 (<line>:1:1) `+`, after 3 cycles with the pointer at 0\n\
+                    [0:255]\n");
+    }
+
+    #[test]
+    fn stop_growing() {
+        let far = ">".repeat(30005);
+        assert_eq!(session(&[":p grow", &far, "+", ":t 1", ":p wrap", ":t 1", "+>", ":t 1"]),
+                   "30004:0 [30005:1]\n30004:0 [30005:1]\n[0:0] 1:0\n");
+        assert_eq!(session(&[":p grow", &far, "+", ":p wrap", "+", ":t 0"]), "[30005:2]\n");
+    }
+
+    #[test]
+    fn quit() {
+        let mut repl = Repl::new(Interpreter::<PolicyTape>::default());
+        let mut out = Vec::new();
+        assert!(!repl.execute(Command::Quit, &mut out).unwrap());
+    }
+}
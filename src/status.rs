@@ -0,0 +1,26 @@
+use super::Error;
+
+/// The state of an interpreter after running part of a program, see
+/// `Interpreter::step` and `Interpreter::run_for`.
+#[derive(Debug)]
+pub enum Status {
+    /// The program has more to execute.
+    Running,
+    /// The program has finished.
+    Halted,
+    /// The program stopped with an error. The program counter is left at
+    /// the instruction which failed, so running again retries it.
+    Error(Error),
+}
+
+impl Status {
+    /// Returns true when the program has more to execute.
+    pub fn is_running(&self) -> bool {
+        matches!(*self, Status::Running)
+    }
+
+    /// Returns true when the program has finished.
+    pub fn is_halted(&self) -> bool {
+        matches!(*self, Status::Halted)
+    }
+}
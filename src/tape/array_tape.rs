@@ -3,33 +3,50 @@ use super::*;
 
 /// A tape with statically allocated cells.
 ///
-/// This tape is implemented with a `[u8]` so it uses the memory for all
-/// 30,000 cells all the time, but allocation is done up front. The cells
-/// are of type `u8`, and the tape's length is forced to be no greater
-/// than `TAPE_LENGTH` so this tape is *nice*.
-pub struct ArrayTape {
-    cells: [u8; TAPE_LENGTH],
+/// This tape is implemented with a `[C]` so it uses the memory for all of
+/// its cells all the time, but allocation is done up front. The cells are of
+/// type `u8` by default, and the tape is `TAPE_LENGTH` cells long unless
+/// made with `with_length`. Values and the pointer going out of range are
+/// errors.
+pub struct ArrayTape<C: Integer = u8> {
+    cells: Box<[C]>,
     ptr: usize,
+    // The end of the touched cells.
+    end: usize,
 }
 
-impl Default for ArrayTape {
+impl<C: Integer> Default for ArrayTape<C> {
     fn default() -> Self {
+        ArrayTape::with_length(TAPE_LENGTH)
+    }
+}
+
+impl<C: Integer> ArrayTape<C> {
+    /// Create a tape of `length` cells, which must be at least one.
+    pub fn with_length(length: usize) -> ArrayTape<C> {
+        assert!(length > 0, "a tape needs a cell");
         ArrayTape {
-            cells: [0; TAPE_LENGTH],
+            cells: vec![C::default(); length].into_boxed_slice(),
             ptr: 0,
+            end: 1,
         }
     }
+
+    /// The number of cells in the tape.
+    pub fn length(&self) -> usize {
+        self.cells.len()
+    }
 }
 
-impl Tape for ArrayTape {
-    type Cell = u8;
+impl<C: Integer> Tape for ArrayTape<C> {
+    type Cell = C;
 
-    fn is_nice() -> bool {
+    fn is_nice(&self) -> bool {
         true
     }
 
     fn inc_val(&mut self) -> Result<Self::Cell, Error> {
-        match self.checked_add(1) {
+        match self.checked_add_val(1) {
             Some(v) => {
                 **self = v;
                 Ok(v)
@@ -39,7 +56,7 @@ impl Tape for ArrayTape {
     }
 
     fn dec_val(&mut self) -> Result<Self::Cell, Error> {
-        match self.checked_sub(1) {
+        match self.checked_add_val(-1) {
             Some(v) => {
                 **self = v;
                 Ok(v)
@@ -50,8 +67,9 @@ impl Tape for ArrayTape {
 
     fn inc_ptr(&mut self) -> Result<usize, Error> {
         match self.ptr.checked_add(1) {
-            Some(v) if v < TAPE_LENGTH => {
+            Some(v) if v < self.cells.len() => {
                 self.ptr = v;
+                self.end = self.end.max(v + 1);
                 Ok(v)
             },
             _ => Err(Error::PtrOverflow),
@@ -60,27 +78,87 @@ impl Tape for ArrayTape {
 
     fn dec_ptr(&mut self) -> Result<usize, Error> {
         match self.ptr.checked_sub(1) {
-            Some(v) if v < TAPE_LENGTH => {
+            Some(v) => {
                 self.ptr = v;
                 Ok(v)
             },
             _ => Err(Error::PtrUnderflow),
         }
     }
+
+    fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    fn get(&self, index: usize) -> Option<C> {
+        self.cells.get(index).copied()
+    }
+
+    fn set(&mut self, index: usize, value: C) -> Result<(), Error> {
+        match self.cells.get_mut(index) {
+            Some(cell) => {
+                *cell = value;
+                self.end = self.end.max(index + 1);
+                Ok(())
+            },
+            None => Err(Error::PtrOverflow),
+        }
+    }
+
+    fn touched(&self) -> Range<usize> {
+        0..self.end
+    }
+
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        match self.checked_add_val(n) {
+            Some(v) => {
+                **self = v;
+                Ok(())
+            },
+            None if n > 0 => {
+                **self = C::MAX;
+                Err(Error::ValOverflow)
+            },
+            None => {
+                **self = C::MIN;
+                Err(Error::ValUnderflow)
+            },
+        }
+    }
+
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        let v = self.ptr as isize + n;
+        if v >= self.cells.len() as isize {
+            self.ptr = self.cells.len() - 1;
+            self.end = self.cells.len();
+            Err(Error::PtrOverflow)
+        } else if v < 0 {
+            self.ptr = 0;
+            Err(Error::PtrUnderflow)
+        } else {
+            let v = v as usize;
+            self.ptr = v;
+            self.end = self.end.max(v + 1);
+            Ok(())
+        }
+    }
 }
 
-impl ops::Deref for ArrayTape {
-    type Target = u8;
+impl<C: Integer> ops::Deref for ArrayTape<C> {
+    type Target = C;
 
     fn deref(&self) -> &Self::Target {
         &self.cells[self.ptr]
     }
 }
 
-impl ops::DerefMut for ArrayTape {
-    fn deref_mut(&mut self) -> &mut u8 {
+impl<C: Integer> ops::DerefMut for ArrayTape<C> {
+    fn deref_mut(&mut self) -> &mut C {
         &mut self.cells[self.ptr]
     }
 }
 
-tape_tests!(ArrayTape);
+tape_tests!(tests, ArrayTape);
+tape_tests!(tests_i16, ArrayTape<i16>);
+tape_tests!(tests_u64, ArrayTape<u64>);
+tape_tests!(tests_short, ArrayTape, Some(100), ArrayTape::with_length(100));
@@ -0,0 +1,287 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use super::{Cell, Format};
+
+/// An arbitrary precision integer, the cells of a `BigTape`.
+///
+/// Only what brainfuck needs is implemented, adding small numbers, and
+/// converting to and from bytes and primitive integers.
+///
+/// ```
+/// use brainfuck::tape::Big;
+///
+/// let mut n = Big::from(u64::MAX);
+/// n.add(1);
+/// assert_eq!(n.to_string(), "18446744073709551616");
+/// assert_eq!(format!("{:x}", n), "10000000000000000");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Big {
+    negative: bool,
+    // The magnitude in base 2^32, least significant limb first, without
+    // trailing zeros. Zero has no limbs, and isn't negative.
+    limbs: Vec<u32>,
+}
+
+impl Big {
+    /// Add `n`, subtracting when it's negative.
+    pub fn add(&mut self, n: i128) {
+        let magnitude = n.unsigned_abs();
+        if n == 0 {
+            return
+        }
+        if self.limbs.is_empty() || self.negative == (n < 0) {
+            self.negative = n < 0;
+            self.add_magnitude(magnitude);
+        } else {
+            match self.compare_magnitude(magnitude) {
+                Ordering::Greater => self.sub_magnitude(magnitude),
+                Ordering::Equal => self.limbs.clear(),
+                Ordering::Less => {
+                    // The magnitude fits, since it's less than `n`'s.
+                    let rest = magnitude - self.to_u128().expect("less than a u128");
+                    *self = Big::from_magnitude(n < 0, rest);
+                },
+            }
+        }
+        self.normalize();
+    }
+
+    /// Returns true when the value is less than `0`.
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The value as an `i128`, or `None` when it's out of range.
+    pub fn to_i128(&self) -> Option<i128> {
+        let magnitude = self.to_u128()?;
+        if self.negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    fn from_magnitude(negative: bool, magnitude: u128) -> Big {
+        let mut big = Big {
+            negative,
+            limbs: (0..4).map(|i| (magnitude >> (32 * i)) as u32).collect(),
+        };
+        big.normalize();
+        big
+    }
+
+    /// The magnitude as a `u128`, if it fits.
+    fn to_u128(&self) -> Option<u128> {
+        if self.limbs.len() > 4 {
+            return None
+        }
+        Some(self.limbs.iter().rev().fold(0, |n, &limb| n << 32 | limb as u128))
+    }
+
+    fn compare_magnitude(&self, magnitude: u128) -> Ordering {
+        match self.to_u128() {
+            Some(m) => m.cmp(&magnitude),
+            None => Ordering::Greater,
+        }
+    }
+
+    fn add_magnitude(&mut self, magnitude: u128) {
+        let mut carry = magnitude;
+        let mut i = 0;
+        while carry != 0 {
+            if i == self.limbs.len() {
+                self.limbs.push(0);
+            }
+            let sum = self.limbs[i] as u128 + (carry & u32::MAX as u128);
+            self.limbs[i] = sum as u32;
+            carry = (carry >> 32) + (sum >> 32);
+            i += 1;
+        }
+    }
+
+    /// Subtract from the magnitude, which must be at least as large.
+    fn sub_magnitude(&mut self, magnitude: u128) {
+        let mut borrow = magnitude;
+        let mut i = 0;
+        while borrow != 0 {
+            let limb = self.limbs[i] as u128;
+            let take = borrow & u32::MAX as u128;
+            borrow >>= 32;
+            if limb >= take {
+                self.limbs[i] = (limb - take) as u32;
+            } else {
+                self.limbs[i] = (limb + (1 << 32) - take) as u32;
+                borrow += 1;
+            }
+            i += 1;
+        }
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        if self.limbs.is_empty() {
+            self.negative = false;
+        }
+    }
+}
+
+impl From<i64> for Big {
+    fn from(n: i64) -> Big {
+        Big::from_magnitude(n < 0, n.unsigned_abs() as u128)
+    }
+}
+
+impl From<u64> for Big {
+    fn from(n: u64) -> Big {
+        Big::from_magnitude(false, n as u128)
+    }
+}
+
+impl Cell for Big {
+    fn from_byte(byte: u8) -> Self {
+        Big::from(byte as u64)
+    }
+
+    fn to_byte(&self) -> u8 {
+        let low = self.limbs.first().copied().unwrap_or(0) as u8;
+        // The lowest bits of the two's complement.
+        if self.negative { low.wrapping_neg() } else { low }
+    }
+
+    fn from_i128(n: i128) -> Option<Self> {
+        Some(Big::from_magnitude(n < 0, n.unsigned_abs()))
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        if self.negative {
+            return None
+        }
+        u64::try_from(self.to_u128()?).ok()
+    }
+
+    fn all_ones() -> Self {
+        Big::from(-1i64)
+    }
+
+    fn width(_: Format) -> usize {
+        1
+    }
+}
+
+impl fmt::Display for Big {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Divide out 9 decimal digits at a time.
+        const BASE: u64 = 1_000_000_000;
+        let mut limbs = self.limbs.clone();
+        let mut groups = Vec::new();
+        while !limbs.is_empty() {
+            let mut rest = 0;
+            for limb in limbs.iter_mut().rev() {
+                let n = rest << 32 | *limb as u64;
+                *limb = (n / BASE) as u32;
+                rest = n % BASE;
+            }
+            groups.push(rest);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+        let mut digits = String::new();
+        if self.negative {
+            digits.push('-');
+        }
+        match groups.split_last() {
+            Some((first, rest)) => {
+                digits.push_str(&first.to_string());
+                for group in rest.iter().rev() {
+                    digits.push_str(&format!("{:09}", group));
+                }
+            },
+            None => digits.push('0'),
+        }
+        f.pad(&digits)
+    }
+}
+
+impl fmt::LowerHex for Big {
+    /// Negative values are written with a `-`, not in two's complement.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut digits = String::new();
+        if self.negative {
+            digits.push('-');
+        }
+        match self.limbs.split_last() {
+            Some((first, rest)) => {
+                digits.push_str(&format!("{:x}", first));
+                for limb in rest.iter().rev() {
+                    digits.push_str(&format!("{:08x}", limb));
+                }
+            },
+            None => digits.push('0'),
+        }
+        f.pad(&digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add() {
+        let mut n = Big::default();
+        n.add(-5);
+        assert_eq!(n.to_i128(), Some(-5));
+        n.add(3);
+        assert_eq!(n.to_i128(), Some(-2));
+        n.add(2);
+        assert_eq!(n, Big::default());
+        n.add(i128::MAX);
+        n.add(i128::MAX);
+        assert_eq!(n.to_i128(), None);
+        n.add(i128::MIN);
+        assert_eq!(n.to_i128(), Some(i128::MAX - 1));
+    }
+
+    #[test]
+    fn carries() {
+        let mut n = Big::from(u32::MAX as u64);
+        n.add(1);
+        assert_eq!(n.limbs, vec![0, 1]);
+        n.add(-1);
+        assert_eq!(n.limbs, vec![u32::MAX]);
+    }
+
+    #[test]
+    fn display() {
+        let mut n = Big::from(u64::MAX);
+        n.add(u64::MAX as i128);
+        assert_eq!(n.to_string(), "36893488147419103230");
+        n.add(-(u64::MAX as i128) * 3);
+        assert_eq!(n.to_string(), "-18446744073709551615");
+        assert_eq!(Big::default().to_string(), "0");
+        assert_eq!(format!("{:>4}", Big::from(7u64)), "   7");
+        assert_eq!(Big::from(1_000_000_000u64).to_string(), "1000000000");
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(format!("{:x}", Big::from(-255i64)), "-ff");
+        assert_eq!(format!("{:x}", Big::default()), "0");
+    }
+
+    #[test]
+    fn cell() {
+        assert_eq!(Big::from_byte(255).to_string(), "255");
+        assert_eq!(Big::from(-1i64).to_byte(), 255);
+        assert_eq!(Big::from(258u64).to_byte(), 2);
+        assert_eq!(Big::from(-1i64).to_u64(), None);
+        assert_eq!(Big::from(u64::MAX).to_u64(), Some(u64::MAX));
+        assert!(Big::default().is_zero());
+        assert_eq!(Big::all_ones(), Big::from(-1i64));
+    }
+}
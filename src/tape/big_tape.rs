@@ -0,0 +1,207 @@
+use std::ops;
+use super::*;
+
+/// A tape with arbitrary precision cells.
+///
+/// This tape is implemented with a `Vec<Big>` so it only uses memory it
+/// needs, like `VecTape`. The cells never overflow, so programs run with the
+/// "ideal" semantics of unbounded integers. Values below `0` are allowed by
+/// default, or errors with `negatives`. The tape is `TAPE_LENGTH` cells long
+/// unless made with `with_length`.
+///
+/// ```
+/// use brainfuck::Interpreter;
+/// use brainfuck::program::Program;
+/// use brainfuck::tape::BigTape;
+///
+/// // 16 * 16 doesn't fit in a byte.
+/// let program = Program::parse("++++++++++++++++[>++++++++++++++++<-]>").unwrap();
+/// let mut interp = Interpreter::<BigTape>::default();
+/// interp.load(program).run().unwrap();
+/// assert_eq!(interp.tape().to_string(), "256");
+/// ```
+pub struct BigTape {
+    cells: Vec<Big>,
+    ptr: usize,
+    length: usize,
+    negatives: bool,
+}
+
+impl Default for BigTape {
+    fn default() -> Self {
+        BigTape::with_length(TAPE_LENGTH)
+    }
+}
+
+impl BigTape {
+    /// Create a tape of `length` cells, which must be at least one.
+    pub fn with_length(length: usize) -> BigTape {
+        assert!(length > 0, "a tape needs a cell");
+        BigTape {
+            // Create the first cell.
+            cells: vec![Big::default()],
+            ptr: 0,
+            length,
+            negatives: true,
+        }
+    }
+
+    /// The number of cells in the tape.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Allow values below `0`, which is the default. Otherwise going below
+    /// `0` is an `Error::ValUnderflow`.
+    pub fn negatives(&mut self, allow: bool) -> &mut Self {
+        self.negatives = allow;
+        self
+    }
+}
+
+impl Tape for BigTape {
+    type Cell = Big;
+
+    fn is_nice(&self) -> bool {
+        true
+    }
+
+    fn inc_val(&mut self) -> Result<Self::Cell, Error> {
+        self.add_val(1)?;
+        Ok((**self).clone())
+    }
+
+    fn dec_val(&mut self) -> Result<Self::Cell, Error> {
+        self.add_val(-1)?;
+        Ok((**self).clone())
+    }
+
+    fn inc_ptr(&mut self) -> Result<usize, Error> {
+        match self.ptr.checked_add(1) {
+            Some(v) if v < self.length => {
+                if v >= self.cells.len() {
+                    // Add another cell dynamically.
+                    self.cells.push(Big::default());
+                }
+                self.ptr = v;
+                Ok(v)
+            },
+            _ => Err(Error::PtrOverflow),
+        }
+    }
+
+    fn dec_ptr(&mut self) -> Result<usize, Error> {
+        match self.ptr.checked_sub(1) {
+            Some(v) => {
+                self.ptr = v;
+                Ok(v)
+            },
+            None => Err(Error::PtrUnderflow),
+        }
+    }
+
+    fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    fn get(&self, index: usize) -> Option<Big> {
+        if index < self.length {
+            // Cells which haven't been allocated yet are `0`.
+            Some(self.cells.get(index).cloned().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, index: usize, value: Big) -> Result<(), Error> {
+        if index >= self.length {
+            return Err(Error::PtrOverflow)
+        }
+        if value.is_negative() && !self.negatives {
+            return Err(Error::ValUnderflow)
+        }
+        if index >= self.cells.len() {
+            self.cells.resize(index + 1, Big::default());
+        }
+        self.cells[index] = value;
+        Ok(())
+    }
+
+    fn touched(&self) -> Range<usize> {
+        0..self.cells.len()
+    }
+
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        let negatives = self.negatives;
+        let cell = &mut **self;
+        cell.add(n as i128);
+        if cell.is_negative() && !negatives {
+            *cell = Big::default();
+            return Err(Error::ValUnderflow)
+        }
+        Ok(())
+    }
+
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        let v = self.ptr as isize + n;
+        if v >= self.length as isize {
+            self.cells.resize(self.length, Big::default());
+            self.ptr = self.length - 1;
+            Err(Error::PtrOverflow)
+        } else if v < 0 {
+            self.ptr = 0;
+            Err(Error::PtrUnderflow)
+        } else {
+            let v = v as usize;
+            if v >= self.cells.len() {
+                self.cells.resize(v + 1, Big::default());
+            }
+            self.ptr = v;
+            Ok(())
+        }
+    }
+}
+
+impl ops::Deref for BigTape {
+    type Target = Big;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cells[self.ptr]
+    }
+}
+
+impl ops::DerefMut for BigTape {
+    fn deref_mut(&mut self) -> &mut Big {
+        &mut self.cells[self.ptr]
+    }
+}
+
+tape_tests!(tests, BigTape);
+tape_tests!(tests_short, BigTape, Some(100), BigTape::with_length(100));
+
+#[cfg(test)]
+mod big_tests {
+    use super::*;
+
+    #[test]
+    fn unbounded() {
+        let mut tape = BigTape::default();
+        tape.add_val(isize::MAX).unwrap();
+        tape.add_val(isize::MAX).unwrap();
+        tape.inc_val().unwrap();
+        assert_eq!(tape.to_string(), "18446744073709551615");
+    }
+
+    #[test]
+    fn negatives() {
+        let mut tape = BigTape::default();
+        assert_eq!(tape.dec_val().unwrap().to_string(), "-1");
+        tape.negatives(false);
+        tape.inc_ptr().unwrap();
+        assert!(matches!(tape.dec_val(), Err(Error::ValUnderflow)));
+        assert!(tape.is_zero());
+        tape.add_val(2).unwrap();
+        assert!(matches!(tape.add_val(-3), Err(Error::ValUnderflow)));
+        assert!(tape.set(0, Big::from(-1i64)).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::convert::TryFrom;
+use std::fmt;
+use super::Format;
+
+/// The values held by the cells of a tape.
+///
+/// The `Input` instruction stores a byte in a cell as its unsigned value, so
+/// `255` reads into an `i8` as `-1`. The `Output` instruction writes the
+/// lowest 8 bits of a cell, in two's complement.
+///
+/// Cells are fixed width integers, see `Integer`, or the unbounded `Big`.
+pub trait Cell: Clone + Default + Eq + fmt::Debug + fmt::Display + fmt::LowerHex + 'static {
+    /// The value read from the given byte of input.
+    fn from_byte(byte: u8) -> Self;
+
+    /// The byte output for this value, its lowest 8 bits.
+    fn to_byte(&self) -> u8;
+
+    /// The value `n`, or `None` when it's out of range.
+    fn from_i128(n: i128) -> Option<Self>;
+
+    /// The value as a `u64`, or `None` when it's negative or too large.
+    fn to_u64(&self) -> Option<u64>;
+
+    /// The value with every bit set, `-1` for signed and unbounded cells.
+    fn all_ones() -> Self;
+
+    /// The number of characters every value takes in the given format. Dumps
+    /// pad values to at least this width.
+    fn width(format: Format) -> usize;
+
+    /// Returns true when the value is `0`.
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Cells which are fixed width integers, signed or unsigned.
+pub trait Integer: Cell + Copy + Ord {
+    /// The smallest value of a cell.
+    const MIN: Self;
+    /// The largest value of a cell.
+    const MAX: Self;
+    /// The number of bits in a cell.
+    const BITS: u32;
+
+    /// The value as an `i128`, which holds every value of every integer.
+    fn to_i128(self) -> i128;
+
+    /// The value congruent to `n` modulo `2^BITS`.
+    fn wrapping_from_i128(n: i128) -> Self;
+
+    /// Add `n`, or `None` when the result is out of range.
+    fn checked_add_val(self, n: isize) -> Option<Self> {
+        Self::from_i128(self.to_i128() + n as i128)
+    }
+
+    /// Add `n`, wrapping around at the bounds.
+    fn wrapping_add_val(self, n: isize) -> Self {
+        Self::wrapping_from_i128(self.to_i128() + n as i128)
+    }
+}
+
+macro_rules! integer {
+    ($($t:ident)*) => {$(
+        impl Cell for $t {
+            fn from_byte(byte: u8) -> Self {
+                Self::wrapping_from_i128(byte as i128)
+            }
+
+            fn to_byte(&self) -> u8 {
+                *self as u8
+            }
+
+            fn from_i128(n: i128) -> Option<Self> {
+                $t::try_from(n).ok()
+            }
+
+            fn to_u64(&self) -> Option<u64> {
+                u64::try_from(*self).ok()
+            }
+
+            fn all_ones() -> Self {
+                !0
+            }
+
+            fn width(format: Format) -> usize {
+                match format {
+                    Format::Hex => ($t::BITS / 4) as usize,
+                    Format::Decimal => {
+                        $t::MIN.to_string().len().max($t::MAX.to_string().len())
+                    },
+                }
+            }
+        }
+
+        impl Integer for $t {
+            const MIN: Self = $t::MIN;
+            const MAX: Self = $t::MAX;
+            const BITS: u32 = $t::BITS;
+
+            fn to_i128(self) -> i128 {
+                self as i128
+            }
+
+            fn wrapping_from_i128(n: i128) -> Self {
+                // Truncating is arithmetic modulo `2^BITS`.
+                n as $t
+            }
+        }
+    )*}
+}
+
+integer!(u8 u16 u32 u64 i8 i16 i32 i64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes() {
+        assert_eq!(i8::from_byte(255), -1);
+        assert_eq!(i16::from_byte(255), 255);
+        assert_eq!(0x1234u16.to_byte(), 0x34);
+        assert_eq!((-1i32).to_byte(), 255);
+    }
+
+    #[test]
+    fn to_u64() {
+        assert_eq!((-1i8).to_u64(), None);
+        assert_eq!(u64::MAX.to_u64(), Some(u64::MAX));
+        assert_eq!(300u16.to_u64(), Some(300));
+    }
+
+    #[test]
+    fn add_val() {
+        assert_eq!(250u8.checked_add_val(10), None);
+        assert_eq!(250u8.wrapping_add_val(10), 4);
+        assert_eq!(0i8.checked_add_val(-128), Some(-128));
+        assert_eq!(0i8.checked_add_val(-129), None);
+        assert_eq!(u64::MAX.wrapping_add_val(1), 0);
+        assert_eq!(0u16.wrapping_add_val(-1), u16::MAX);
+    }
+
+    #[test]
+    fn all_ones() {
+        assert_eq!(u32::all_ones(), u32::MAX);
+        assert_eq!(i16::all_ones(), -1);
+    }
+
+    #[test]
+    fn width() {
+        assert_eq!(u8::width(Format::Hex), 2);
+        assert_eq!(u8::width(Format::Decimal), 3);
+        assert_eq!(i16::width(Format::Decimal), 6);
+    }
+}
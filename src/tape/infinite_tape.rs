@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::mem;
+use std::ops;
+use super::*;
+
+/// A tape which grows in both directions.
+///
+/// This tape is implemented with a `VecDeque<C>` so it only uses memory it
+/// needs, and cells are added on whichever end the pointer runs off. There
+/// is no limit on its length other than an optional memory budget, see
+/// `budget`. Values going out of range are errors.
+///
+/// Indices count from the leftmost cell the pointer has reached, so growing
+/// to the left shifts every index up by one. The cell the pointer started
+/// on is at `origin`.
+///
+/// ```
+/// use brainfuck::tape::{InfiniteTape, Tape};
+///
+/// let mut tape: InfiniteTape = InfiniteTape::default();
+/// tape.move_ptr(-3).unwrap();
+/// assert_eq!(tape.ptr(), 0);
+/// assert_eq!(tape.origin(), 3);
+/// assert_eq!(tape.touched(), 0..4);
+/// ```
+pub struct InfiniteTape<C: Integer = u8> {
+    cells: VecDeque<C>,
+    ptr: usize,
+    origin: usize,
+    // The most cells the tape may grow to.
+    limit: usize,
+}
+
+impl<C: Integer> Default for InfiniteTape<C> {
+    fn default() -> Self {
+        let mut cells = VecDeque::new();
+        // Create the first cell.
+        cells.push_back(C::default());
+        InfiniteTape {
+            cells,
+            ptr: 0,
+            origin: 0,
+            limit: usize::MAX,
+        }
+    }
+}
+
+impl<C: Integer> InfiniteTape<C> {
+    /// Limit the memory used by the cells to about `bytes`, or lift the
+    /// limit with `None`, which is the default. Moving the pointer off the
+    /// end of a tape that can't grow is an `Error::PtrOverflow` on the right
+    /// and an `Error::PtrUnderflow` on the left. Cells the tape already has
+    /// are kept.
+    pub fn budget(&mut self, bytes: Option<usize>) -> &mut Self {
+        self.limit = match bytes {
+            // There is always at least the current cell.
+            Some(bytes) => (bytes / mem::size_of::<C>()).max(1),
+            None => usize::MAX,
+        };
+        self
+    }
+
+    /// The index of the cell the pointer started on.
+    pub fn origin(&self) -> usize {
+        self.origin
+    }
+
+    /// Add up to `n` cells to the left, returning how many were added.
+    fn grow_left(&mut self, n: usize) -> usize {
+        let n = n.min(self.limit.saturating_sub(self.cells.len()));
+        for _ in 0..n {
+            self.cells.push_front(C::default());
+        }
+        self.ptr += n;
+        self.origin += n;
+        n
+    }
+
+    /// Add cells to the right, up to `len` cells in total. Returns false
+    /// when the budget doesn't allow it.
+    fn grow_right(&mut self, len: usize) -> bool {
+        let fits = len <= self.limit;
+        let len = len.min(self.limit);
+        if len > self.cells.len() {
+            self.cells.resize(len, C::default());
+        }
+        fits
+    }
+}
+
+impl<C: Integer> Tape for InfiniteTape<C> {
+    type Cell = C;
+
+    fn is_nice(&self) -> bool {
+        true
+    }
+
+    fn inc_val(&mut self) -> Result<Self::Cell, Error> {
+        match self.checked_add_val(1) {
+            Some(v) => {
+                **self = v;
+                Ok(v)
+            },
+            None => Err(Error::ValOverflow)
+        }
+    }
+
+    fn dec_val(&mut self) -> Result<Self::Cell, Error> {
+        match self.checked_add_val(-1) {
+            Some(v) => {
+                **self = v;
+                Ok(v)
+            },
+            None => Err(Error::ValUnderflow),
+        }
+    }
+
+    fn inc_ptr(&mut self) -> Result<usize, Error> {
+        let v = self.ptr.checked_add(1).ok_or(Error::PtrOverflow)?;
+        if !self.grow_right(v + 1) {
+            return Err(Error::PtrOverflow)
+        }
+        self.ptr = v;
+        Ok(v)
+    }
+
+    fn dec_ptr(&mut self) -> Result<usize, Error> {
+        if self.ptr == 0 && self.grow_left(1) == 0 {
+            return Err(Error::PtrUnderflow)
+        }
+        self.ptr -= 1;
+        Ok(self.ptr)
+    }
+
+    fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    fn get(&self, index: usize) -> Option<C> {
+        if index < self.limit {
+            // Cells which haven't been allocated yet are `0`.
+            Some(self.cells.get(index).copied().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, index: usize, value: C) -> Result<(), Error> {
+        if index >= self.limit || !self.grow_right(index.saturating_add(1)) {
+            return Err(Error::PtrOverflow)
+        }
+        self.cells[index] = value;
+        Ok(())
+    }
+
+    fn touched(&self) -> Range<usize> {
+        0..self.cells.len()
+    }
+
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        match self.checked_add_val(n) {
+            Some(v) => {
+                **self = v;
+                Ok(())
+            },
+            None if n > 0 => {
+                **self = C::MAX;
+                Err(Error::ValOverflow)
+            },
+            None => {
+                **self = C::MIN;
+                Err(Error::ValUnderflow)
+            },
+        }
+    }
+
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        if n >= 0 {
+            let v = self.ptr.saturating_add(n as usize);
+            if !self.grow_right(v.saturating_add(1)) {
+                self.ptr = self.cells.len() - 1;
+                return Err(Error::PtrOverflow)
+            }
+            self.ptr = v;
+        } else {
+            let n = n.unsigned_abs();
+            if n > self.ptr {
+                let missing = n - self.ptr;
+                if self.grow_left(missing) < missing {
+                    self.ptr = 0;
+                    return Err(Error::PtrUnderflow)
+                }
+            }
+            self.ptr -= n;
+        }
+        Ok(())
+    }
+}
+
+impl<C: Integer> ops::Deref for InfiniteTape<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cells[self.ptr]
+    }
+}
+
+impl<C: Integer> ops::DerefMut for InfiniteTape<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.cells[self.ptr]
+    }
+}
+
+tape_tests!(tests, InfiniteTape, None);
+tape_tests!(tests_i16, InfiniteTape<i16>, None);
+
+#[cfg(test)]
+mod infinite_tests {
+    use super::*;
+
+    #[test]
+    fn left() {
+        let mut tape: InfiniteTape = InfiniteTape::default();
+        *tape = 1;
+        tape.dec_ptr().unwrap();
+        *tape = 2;
+        assert_eq!(tape.ptr(), 0);
+        assert_eq!(tape.origin(), 1);
+        tape.move_ptr(-2).unwrap();
+        assert_eq!(tape.origin(), 3);
+        tape.move_ptr(5).unwrap();
+        let cells: Vec<_> = tape.cells().collect();
+        assert_eq!(cells, vec![(0, 0), (1, 0), (2, 2), (3, 1), (4, 0), (5, 0)]);
+    }
+
+    #[test]
+    fn budget() {
+        let mut tape: InfiniteTape<u16> = InfiniteTape::default();
+        tape.budget(Some(8));
+        tape.move_ptr(-2).unwrap();
+        tape.move_ptr(3).unwrap();
+        assert!(matches!(tape.inc_ptr(), Err(Error::PtrOverflow)));
+        assert_eq!(tape.ptr(), 3);
+        assert!(matches!(tape.move_ptr(-5), Err(Error::PtrUnderflow)));
+        assert_eq!(tape.ptr(), 0);
+        assert_eq!(tape.get(4), None);
+        assert!(tape.set(4, 1).is_err());
+        tape.budget(None);
+        tape.move_ptr(-1).unwrap();
+        assert_eq!(tape.touched(), 0..5);
+    }
+
+    #[test]
+    fn budget_limits_growth() {
+        let mut tape: InfiniteTape = InfiniteTape::default();
+        tape.budget(Some(3));
+        assert!(matches!(tape.move_ptr(10), Err(Error::PtrOverflow)));
+        assert_eq!(tape.ptr(), 2);
+        assert!(matches!(tape.move_ptr(-10), Err(Error::PtrUnderflow)));
+        assert_eq!(tape.ptr(), 0);
+        assert_eq!(tape.origin(), 0);
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt::Write;
+use std::ops::Range;
+use std::vec;
+use super::{Cell, Tape};
+
+/// An iterator over the index and value of each touched cell of a tape, see
+/// `Tape::cells`.
+pub struct Cells<'a, T: Tape + ?Sized> {
+    tape: &'a T,
+    indices: Indices,
+}
+
+/// The indices of the cells to visit.
+enum Indices {
+    Range(Range<usize>),
+    Listed(vec::IntoIter<usize>),
+}
+
+impl<'a, T: Tape + ?Sized> Cells<'a, T> {
+    /// Visit every cell in the given range.
+    pub(super) fn range(tape: &'a T, range: Range<usize>) -> Self {
+        Cells { tape, indices: Indices::Range(range) }
+    }
+
+    /// Visit only the cells at the given indices, which must be in order.
+    pub(super) fn listed(tape: &'a T, indices: Vec<usize>) -> Self {
+        Cells { tape, indices: Indices::Listed(indices.into_iter()) }
+    }
+}
+
+impl<'a, T: Tape + ?Sized> Iterator for Cells<'a, T> {
+    type Item = (usize, T::Cell);
+
+    fn next(&mut self) -> Option<(usize, T::Cell)> {
+        let index = match self.indices {
+            Indices::Range(ref mut range) => range.next()?,
+            Indices::Listed(ref mut indices) => indices.next()?,
+        };
+        Some((index, self.tape.get(index).expect("touched cells are in the tape")))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.indices {
+            Indices::Range(ref range) => range.size_hint(),
+            Indices::Listed(ref indices) => indices.size_hint(),
+        }
+    }
+}
+
+/// How the values of cells are written in a dump, see `Tape::dump`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Hexadecimal, with all the digits of a cell, like `0f` for `u8`. Signed
+    /// cells are written in two's complement.
+    Hex,
+    /// Decimal, padded to the width of the longest value of a cell.
+    Decimal,
+}
+
+/// The number of cells on each line of a dump.
+const ROW: usize = 16;
+
+pub(super) fn dump<T: Tape + ?Sized>(tape: &T, format: Format) -> String {
+    let touched = tape.touched();
+    let label = touched.end.saturating_sub(1).to_string().len();
+    let ptr = tape.ptr();
+    // Values are padded to the widest of them.
+    let render = |value: T::Cell| match format {
+        Format::Hex => format!("{:x}", value),
+        Format::Decimal => value.to_string(),
+    };
+    let width = tape.cells()
+        .map(|(_, v)| render(v).len())
+        .fold(T::Cell::width(format), usize::max);
+    // Only the rows with a cell listed by `cells`, which is all of them
+    // unless the tape skips cells which are `0`.
+    let mut rows: Vec<usize> = tape.cells().map(|(i, _)| i - i % ROW).collect();
+    rows.dedup();
+    let mut out = String::new();
+    for row in rows {
+        write!(out, "{:0w$}", row, w = label).expect("writing to a String");
+        for index in row..(row + ROW).min(touched.end) {
+            let value = tape.get(index).expect("touched cells are in the tape");
+            // The current cell is marked in place of the space before it.
+            out.push(if index == ptr { '>' } else { ' ' });
+            let value = render(value);
+            match format {
+                // Only unbounded cells are negative in hex, the zeros go
+                // after the sign.
+                Format::Hex => match value.strip_prefix('-') {
+                    Some(digits) => write!(out, "-{:0>1$}", digits, width - 1),
+                    None => write!(out, "{:0>1$}", value, width),
+                },
+                Format::Decimal => write!(out, "{:>1$}", value, width),
+            }.expect("writing to a String");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tape::{Big, BigTape, Tape, VecTape};
+    use super::*;
+
+    #[test]
+    fn widths() {
+        let mut tape = VecTape::<i16>::default();
+        tape.set(1, -1).unwrap();
+        assert_eq!(tape.dump(Format::Hex), "0>0000 ffff\n");
+        assert_eq!(tape.dump(Format::Decimal), "0>     0     -1\n");
+    }
+
+    #[test]
+    fn negative_hex() {
+        let mut tape = BigTape::default();
+        tape.set(0, Big::from(-1i64)).unwrap();
+        tape.set(1, Big::from(0x1ffi64)).unwrap();
+        assert_eq!(tape.dump(Format::Hex), "0>-01 1ff\n");
+    }
+
+    #[test]
+    fn rows() {
+        let mut tape = VecTape::<u8>::default();
+        tape.set(16, 0xab).unwrap();
+        tape.move_ptr(1).unwrap();
+        assert_eq!(tape.dump(Format::Hex),
+                   "00 00>00 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n\
+                    16 ab\n");
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::str::FromStr;
+use super::{PointerPolicy, ValuePolicy};
+
+/// The tapes of this module, for choosing one at runtime, like from the
+/// command line.
+///
+/// Since `Interpreter` is generic over its tape, a kind is matched on to
+/// pick the type to run with.
+///
+/// ```
+/// use brainfuck::tape::Kind;
+///
+/// assert_eq!("mod-array".parse(), Ok(Kind::ModArray));
+/// assert_eq!(Kind::Sparse.to_string(), "sparse");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// `VecTape`, values and the pointer going out of range are errors.
+    Vec,
+    /// `ArrayTape`, values and the pointer going out of range are errors.
+    Array,
+    /// `ModArrayTape`, values and the pointer wrap around.
+    ModArray,
+    /// `BigTape`, values are unbounded, and the pointer going out of range
+    /// is an error.
+    Big,
+    /// `InfiniteTape`, the tape grows in both directions.
+    Infinite,
+    /// `SparseTape`, only cells which aren't `0` are stored.
+    Sparse,
+    /// `PolicyTape`, with a choice of behavior at the bounds.
+    Policy,
+}
+
+impl Kind {
+    /// Every kind of tape, in the order they're listed above.
+    pub const ALL: [Kind; 7] = [
+        Kind::Vec,
+        Kind::Array,
+        Kind::ModArray,
+        Kind::Big,
+        Kind::Infinite,
+        Kind::Sparse,
+        Kind::Policy,
+    ];
+
+    /// The policies for a `PolicyTape` which behaves like this kind of tape,
+    /// apart from how it allocates cells. This is `None` for `Big`, whose
+    /// cells have no bounds, and `Policy`, which has no policies of its own.
+    ///
+    /// ```
+    /// use brainfuck::tape::{Kind, PointerPolicy, ValuePolicy};
+    ///
+    /// assert_eq!(Kind::ModArray.policies(), Some((ValuePolicy::Wrap, PointerPolicy::Wrap)));
+    /// assert_eq!(Kind::Big.policies(), None);
+    /// ```
+    pub fn policies(&self) -> Option<(ValuePolicy, PointerPolicy)> {
+        match *self {
+            Kind::Vec | Kind::Array | Kind::Sparse => Some((ValuePolicy::Error, PointerPolicy::Error)),
+            Kind::ModArray => Some((ValuePolicy::Wrap, PointerPolicy::Wrap)),
+            Kind::Infinite => Some((ValuePolicy::Error, PointerPolicy::Grow)),
+            Kind::Big | Kind::Policy => None,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Kind::Vec      => write!(f, "vec"),
+            Kind::Array    => write!(f, "array"),
+            Kind::ModArray => write!(f, "mod-array"),
+            Kind::Big      => write!(f, "big"),
+            Kind::Infinite => write!(f, "infinite"),
+            Kind::Sparse   => write!(f, "sparse"),
+            Kind::Policy   => write!(f, "policy"),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    /// Parse a kind of tape from the same names it displays as.
+    fn from_str(s: &str) -> Result<Kind, String> {
+        Kind::ALL.iter()
+            .find(|kind| kind.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown tape `{}`", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for kind in &Kind::ALL {
+            assert_eq!(kind.to_string().parse::<Kind>(), Ok(*kind));
+        }
+        assert!("mod".parse::<Kind>().is_err());
+    }
+}
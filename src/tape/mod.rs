@@ -1,26 +1,39 @@
-use std::ops;
+use std::ops::{self, Range};
 
-/// The number of cells a portable tape contains. Attempts to access above or
-/// below this limit will result in an error.
+/// The number of cells a portable tape contains, and the length of tapes
+/// unless they're made with another. Attempts to access above or below this
+/// limit will result in an error.
 pub const TAPE_LENGTH: usize = 30000;
 
 // Re-exports.
+pub use self::big::Big;
+pub use self::cell::{Cell, Integer};
 pub use self::error::Error;
+pub use self::inspect::{Cells, Format};
+pub use self::kind::Kind;
+pub use self::policy::{PointerPolicy, ValuePolicy};
 pub use self::vec_tape::VecTape;
 pub use self::array_tape::ArrayTape;
 pub use self::mod_array_tape::ModArrayTape;
+pub use self::big_tape::BigTape;
+pub use self::infinite_tape::InfiniteTape;
+pub use self::sparse_tape::SparseTape;
+pub use self::policy_tape::PolicyTape;
 
 /// An interface for the underlying data for brainfuck. Tapes are
-/// conceptually a sequential list of cells, who's values can be
-/// represented as bytes.
-pub trait Tape: ops::Deref<Target=u8> + ops::DerefMut {
-    /// The underlying cell type, that holds the data. This value when
-    /// dereferenced will need to be converted to a `u8`.
-    type Cell;
+/// conceptually a sequential list of cells, who's values are integers, see
+/// `Cell`. The tapes in this module hold `u8` cells by default, and any other
+/// width with a type parameter, like `ModArrayTape<u16>`, except `BigTape`
+/// whose cells are unbounded. Tapes with a fixed length are `TAPE_LENGTH`
+/// cells long by default, and any other length with `with_length`.
+pub trait Tape: ops::Deref<Target=<Self as Tape>::Cell> + ops::DerefMut {
+    /// The underlying cell type, that holds the data. This is the value of
+    /// the current cell when dereferenced.
+    type Cell: Cell;
 
     /// Returns true if this tape is *nice*, meaning that it upholds
-    /// the expectations of most brainfuck programs.
-    fn is_nice() -> bool;
+    /// the expectations of most brainfuck programs with its default length.
+    fn is_nice(&self) -> bool;
 
     /// Increment the value of the current cell by 1.
     fn inc_val(&mut self) -> Result<Self::Cell, Error>;
@@ -33,74 +46,295 @@ pub trait Tape: ops::Deref<Target=u8> + ops::DerefMut {
 
     /// Decrement the location of the pointer by 1 cell.
     fn dec_ptr(&mut self) -> Result<usize, Error>;
+
+    /// The location of the pointer, the index of the current cell.
+    fn ptr(&self) -> usize;
+
+    /// Get the value of the cell at the given index, or `None` when the
+    /// index is outside of the tape.
+    fn get(&self, index: usize) -> Option<Self::Cell>;
+
+    /// Set the value of the cell at the given index, failing when the index
+    /// is outside of the tape.
+    fn set(&mut self, index: usize, value: Self::Cell) -> Result<(), Error>;
+
+    /// The range of cells the pointer has visited, or which have been set.
+    /// Every cell outside of it is `0`.
+    fn touched(&self) -> Range<usize>;
+
+    /// Iterate over the index and value of each touched cell, in order. A
+    /// tape which only stores the cells that aren't `0` may skip the others,
+    /// other than the current cell.
+    fn cells(&self) -> Cells<'_, Self> {
+        Cells::range(self, self.touched())
+    }
+
+    /// Render the touched cells as a dump of 16 cells per line, each line
+    /// starting with the index of its first cell. The current cell is
+    /// marked with a `>`. Lines with no cells listed by `cells` are left out.
+    ///
+    /// ```
+    /// use brainfuck::tape::{Format, Tape, VecTape};
+    ///
+    /// let mut tape: VecTape = VecTape::default();
+    /// tape.set(0, 72).unwrap();
+    /// tape.set(2, 255).unwrap();
+    /// assert_eq!(tape.dump(Format::Hex), "0>48 00 ff\n");
+    /// assert_eq!(tape.dump(Format::Decimal), "0> 72   0 255\n");
+    /// ```
+    fn dump(&self, format: Format) -> String {
+        inspect::dump(self, format)
+    }
+
+    /// Add `n` to the value of the current cell, subtracting when `n` is
+    /// negative. This must behave the same as `n` calls to `inc_val` (or
+    /// `dec_val`), including the state of the tape when one of them would
+    /// fail. The default implementation does exactly that, tapes may
+    /// override it with something faster.
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        for _ in 0..n.unsigned_abs() {
+            if n > 0 { self.inc_val()?; } else { self.dec_val()?; }
+        }
+        Ok(())
+    }
+
+    /// Move the pointer by `n` cells, moving down when `n` is negative. This
+    /// must behave the same as `n` calls to `inc_ptr` (or `dec_ptr`). The
+    /// default implementation does exactly that, tapes may override it with
+    /// something faster.
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        for _ in 0..n.unsigned_abs() {
+            if n > 0 { self.inc_ptr()?; } else { self.dec_ptr()?; }
+        }
+        Ok(())
+    }
+
+    /// Returns true if moving the pointer by `n` always reaches another
+    /// cell, and moving it back by `-n` always returns to the cell it
+    /// started on, unless either move fails. The optimized interpreter
+    /// relies on this to replace loops which move the pointer.
+    fn undoes_move(&self, n: isize) -> bool {
+        n != 0
+    }
 }
 
 macro_rules! tape_tests {
-    ($tape:ident) => {
+    ($name:ident, $tape:ty) => {
+        tape_tests!($name, $tape, Some(TAPE_LENGTH));
+    };
+    ($name:ident, $tape:ty, $length:expr) => {
+        tape_tests!($name, $tape, $length, <$tape>::default());
+    };
+    ($name:ident, $tape:ty, $length:expr, $new:expr) => {
         #[cfg(test)]
-        mod tests {
+        mod $name {
             use super::*;
-            use crate::tape::Tape;
+            use crate::tape::{Cell, Tape};
+
+            // A cell holding `n`, since not every cell is a primitive.
+            fn v(n: i128) -> <$tape as Tape>::Cell {
+                Cell::from_i128(n).unwrap()
+            }
 
             #[test]
             fn new() {
-                let _ = $tape::default();
+                let _ = <$tape>::default();
             }
 
             #[test]
             fn deref() {
-                let tape = $tape::default();
-                assert_eq!(*tape, 0);
+                let tape: $tape = $new;
+                assert_eq!(*tape, v(0));
             }
 
             #[test]
             fn deref_mut() {
-                let mut tape = $tape::default();
+                let mut tape: $tape = $new;
                 tape.inc_val().unwrap();
-                *tape = 20;
-                assert_eq!(*tape, 20);
+                *tape = v(20);
+                assert_eq!(*tape, v(20));
             }
 
             #[test]
             fn inc_val() {
-                let mut tape = $tape::default();
-                *tape = 20;
+                let mut tape: $tape = $new;
+                *tape = v(20);
                 tape.inc_val().unwrap();
-                assert_eq!(*tape, 21);
+                assert_eq!(*tape, v(21));
             }
 
             #[test]
             fn dec_val() {
-                let mut tape = $tape::default();
-                *tape = 20;
+                let mut tape: $tape = $new;
+                *tape = v(20);
                 tape.dec_val().unwrap();
-                assert_eq!(*tape, 19);
+                assert_eq!(*tape, v(19));
             }
 
             #[test]
             fn inc_ptr() {
-                let mut tape = $tape::default();
-                *tape = 20;
+                let mut tape: $tape = $new;
+                *tape = v(20);
                 tape.inc_ptr().unwrap();
-                assert_eq!(*tape, 0);
+                assert_eq!(*tape, v(0));
             }
 
             #[test]
             fn dec_ptr() {
-                let mut tape = $tape::default();
-                *tape = 20;
+                let mut tape: $tape = $new;
+                *tape = v(20);
                 tape.inc_ptr().unwrap();
-                assert_eq!(*tape, 0);
+                assert_eq!(*tape, v(0));
                 tape.dec_ptr().unwrap();
-                assert_eq!(*tape, 20);
+                assert_eq!(*tape, v(20));
+            }
+
+            #[test]
+            fn ptr() {
+                let mut tape: $tape = $new;
+                assert_eq!(tape.ptr(), 0);
+                tape.move_ptr(5).unwrap();
+                assert_eq!(tape.ptr(), 5);
+            }
+
+            #[test]
+            fn get_set() {
+                let mut tape: $tape = $new;
+                assert_eq!(tape.get(7), Some(v(0)));
+                tape.set(7, v(42)).unwrap();
+                assert_eq!(tape.get(7), Some(v(42)));
+                tape.move_ptr(7).unwrap();
+                assert_eq!(*tape, v(42));
+            }
+
+            #[test]
+            fn bounds() {
+                let mut tape: $tape = $new;
+                let length: Option<usize> = $length;
+                match length {
+                    Some(length) => {
+                        assert_eq!(tape.get(length - 1), Some(v(0)));
+                        assert_eq!(tape.get(length), None);
+                        assert!(tape.set(length, v(1)).is_err());
+                    },
+                    None => {
+                        assert_eq!(tape.get(TAPE_LENGTH), Some(v(0)));
+                        tape.set(TAPE_LENGTH, v(1)).unwrap();
+                        assert_eq!(tape.get(TAPE_LENGTH), Some(v(1)));
+                    },
+                }
+            }
+
+            #[test]
+            fn edges() {
+                // Going off the left end either fails in place or lands on a
+                // cell.
+                let mut tape: $tape = $new;
+                *tape = v(1);
+                match tape.dec_ptr() {
+                    Ok(ptr) => assert_eq!(tape.ptr(), ptr),
+                    Err(e) => {
+                        assert!(matches!(e, crate::tape::Error::PtrUnderflow));
+                        assert_eq!(tape.ptr(), 0);
+                    },
+                }
+                assert_eq!(tape.get(tape.ptr()), Some((*tape).clone()));
+                let length: Option<usize> = $length;
+                if let Some(length) = length {
+                    // The same for the right end.
+                    let mut tape: $tape = $new;
+                    tape.move_ptr(length as isize - 1).unwrap();
+                    assert_eq!(tape.ptr(), length - 1);
+                    match tape.inc_ptr() {
+                        Ok(ptr) => assert!(ptr < length),
+                        Err(e) => {
+                            assert!(matches!(e, crate::tape::Error::PtrOverflow));
+                            assert_eq!(tape.ptr(), length - 1);
+                        },
+                    }
+                    assert_eq!(tape.get(tape.ptr()), Some((*tape).clone()));
+                }
+            }
+
+            #[test]
+            fn touched() {
+                let mut tape: $tape = $new;
+                assert_eq!(tape.touched(), 0..1);
+                tape.move_ptr(3).unwrap();
+                tape.move_ptr(-2).unwrap();
+                assert_eq!(tape.touched(), 0..4);
+                tape.set(9, v(1)).unwrap();
+                assert_eq!(tape.touched(), 0..10);
+            }
+
+            #[test]
+            fn cells() {
+                let mut tape: $tape = $new;
+                *tape = v(1);
+                tape.move_ptr(2).unwrap();
+                *tape = v(3);
+                // Cells which are `0` may be skipped.
+                let cells: Vec<_> = tape.cells().filter(|c| c.0 != 1).collect();
+                assert_eq!(cells, vec![(0, v(1)), (2, v(3))]);
+                assert!(tape.cells().all(|(i, c)| tape.get(i) == Some(c)));
+            }
+
+            #[test]
+            fn dump() {
+                let mut tape: $tape = $new;
+                tape.set(17, v(0x2b)).unwrap();
+                tape.move_ptr(1).unwrap();
+                let dump = tape.dump(crate::tape::Format::Hex);
+                let lines: Vec<&str> = dump.lines().collect();
+                assert_eq!(lines.len(), 2);
+                assert_eq!(lines[0].matches('>').count(), 1);
+                assert!(lines[1].starts_with("16 "));
+                assert!(lines[1].ends_with("2b"));
+            }
+
+            #[test]
+            fn add_val() {
+                let mut tape: $tape = $new;
+                tape.add_val(20).unwrap();
+                assert_eq!(*tape, v(20));
+                tape.add_val(-5).unwrap();
+                assert_eq!(*tape, v(15));
+                tape.add_val(0).unwrap();
+                assert_eq!(*tape, v(15));
+            }
+
+            #[test]
+            fn move_ptr() {
+                let mut tape: $tape = $new;
+                tape.move_ptr(3).unwrap();
+                *tape = v(20);
+                tape.move_ptr(-2).unwrap();
+                assert_eq!(*tape, v(0));
+                tape.move_ptr(2).unwrap();
+                assert_eq!(*tape, v(20));
             }
         }
     }
 }
 
+/// Values of cells.
+mod cell;
+
+/// Arbitrary precision integers.
+mod big;
+
 /// Tape errors.
 mod error;
 
+/// Reading whole tapes.
+mod inspect;
+
+/// Choosing tapes at runtime.
+mod kind;
+
+/// What tapes do at their bounds.
+mod policy;
+
 /// A `Vec` based tape.
 mod vec_tape;
 
@@ -110,3 +344,15 @@ mod array_tape;
 /// A `[]` (array) based tape that does value and pointer arithmetic wrapping
 /// in a modular fashion.
 mod mod_array_tape;
+
+/// A `Vec` based tape of arbitrary precision cells.
+mod big_tape;
+
+/// A `VecDeque` based tape that grows in both directions.
+mod infinite_tape;
+
+/// A `HashMap` based tape, storing only the cells that aren't `0`.
+mod sparse_tape;
+
+/// A `VecDeque` based tape with a choice of behavior at its bounds.
+mod policy_tape;
@@ -3,68 +3,196 @@ use super::*;
 
 /// A tape with statically allocated cells.
 ///
-/// This tape is implemented with a `[u8]` so it uses the memory for all
-/// 30,000 cells all the time, but allocation is done up front. The cells
-/// are of type `u8`, and the tape's length is forced to be no greater
-/// than `TAPE_LENGTH` so this tape is *nice*.
-pub struct ModArrayTape {
-    cells: [u8; TAPE_LENGTH],
+/// This tape is implemented with a `[C]` so it uses the memory for all of
+/// its cells all the time, but allocation is done up front. The cells are of
+/// type `u8` by default, and the tape is `TAPE_LENGTH` cells long unless
+/// made with `with_length`. Values wrap around at the bounds of the cell
+/// type, and the pointer wraps around at the ends of the tape.
+pub struct ModArrayTape<C: Integer = u8> {
+    cells: Box<[C]>,
     ptr: usize,
+    // The end of the touched cells.
+    end: usize,
 }
 
-impl Default for ModArrayTape {
+impl<C: Integer> Default for ModArrayTape<C> {
     fn default() -> Self {
+        ModArrayTape::with_length(TAPE_LENGTH)
+    }
+}
+
+impl<C: Integer> ModArrayTape<C> {
+    /// Create a tape of `length` cells, which must be at least one.
+    pub fn with_length(length: usize) -> ModArrayTape<C> {
+        assert!(length > 0, "a tape needs a cell");
         ModArrayTape {
-            cells: [0; TAPE_LENGTH],
+            cells: vec![C::default(); length].into_boxed_slice(),
             ptr: 0,
+            end: 1,
         }
     }
+
+    /// The number of cells in the tape.
+    pub fn length(&self) -> usize {
+        self.cells.len()
+    }
 }
 
-impl Tape for ModArrayTape {
-    type Cell = u8;
+impl<C: Integer> Tape for ModArrayTape<C> {
+    type Cell = C;
 
-    fn is_nice() -> bool {
+    fn is_nice(&self) -> bool {
         true
     }
 
     fn inc_val(&mut self) -> Result<Self::Cell, Error> {
-        let v = self.wrapping_add(1);
+        let v = self.wrapping_add_val(1);
         **self = v;
         Ok(v)
     }
 
     fn dec_val(&mut self) -> Result<Self::Cell, Error> {
-        let v = self.wrapping_sub(1);
+        let v = self.wrapping_add_val(-1);
         **self = v;
         Ok(v)
     }
 
     fn inc_ptr(&mut self) -> Result<usize, Error> {
-        let v = self.ptr.wrapping_add(1);
+        let v = (self.ptr + 1) % self.cells.len();
         self.ptr = v;
+        self.end = self.end.max(v + 1);
         Ok(v)
     }
 
     fn dec_ptr(&mut self) -> Result<usize, Error> {
-        let v = self.ptr.wrapping_sub(1);
+        let v = self.ptr.checked_sub(1).unwrap_or(self.cells.len() - 1);
         self.ptr = v;
+        self.end = self.end.max(v + 1);
         Ok(v)
     }
+
+    fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    fn get(&self, index: usize) -> Option<C> {
+        self.cells.get(index).copied()
+    }
+
+    fn set(&mut self, index: usize, value: C) -> Result<(), Error> {
+        match self.cells.get_mut(index) {
+            Some(cell) => {
+                *cell = value;
+                self.end = self.end.max(index + 1);
+                Ok(())
+            },
+            None => Err(Error::PtrOverflow),
+        }
+    }
+
+    fn touched(&self) -> Range<usize> {
+        0..self.end
+    }
+
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        **self = self.wrapping_add_val(n);
+        Ok(())
+    }
+
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        let length = self.cells.len();
+        let v = self.ptr as i128 + n as i128;
+        if v < 0 || v >= length as i128 {
+            // Every cell is passed on the way around.
+            self.end = length;
+        }
+        self.ptr = v.rem_euclid(length as i128) as usize;
+        self.end = self.end.max(self.ptr + 1);
+        Ok(())
+    }
+
+    fn undoes_move(&self, n: isize) -> bool {
+        // A move all the way around comes back to the same cell.
+        n % self.cells.len() as isize != 0
+    }
 }
 
-impl ops::Deref for ModArrayTape {
-    type Target = u8;
+impl<C: Integer> ops::Deref for ModArrayTape<C> {
+    type Target = C;
 
     fn deref(&self) -> &Self::Target {
         &self.cells[self.ptr]
     }
 }
 
-impl ops::DerefMut for ModArrayTape {
-    fn deref_mut(&mut self) -> &mut u8 {
+impl<C: Integer> ops::DerefMut for ModArrayTape<C> {
+    fn deref_mut(&mut self) -> &mut C {
         &mut self.cells[self.ptr]
     }
 }
 
-tape_tests!(ModArrayTape);
+tape_tests!(tests, ModArrayTape);
+tape_tests!(tests_u32, ModArrayTape<u32>);
+tape_tests!(tests_i8, ModArrayTape<i8>);
+tape_tests!(tests_short, ModArrayTape, Some(100), ModArrayTape::with_length(100));
+
+#[cfg(test)]
+mod wrapping_tests {
+    use super::*;
+
+    #[test]
+    fn dec_ptr_wraps() {
+        let mut tape: ModArrayTape = ModArrayTape::default();
+        assert_eq!(tape.dec_ptr().unwrap(), TAPE_LENGTH - 1);
+        *tape = 7;
+        assert_eq!(tape.touched(), 0..TAPE_LENGTH);
+        assert_eq!(tape.inc_ptr().unwrap(), 0);
+        tape.dec_ptr().unwrap();
+        assert_eq!(*tape, 7);
+    }
+
+    #[test]
+    fn inc_ptr_wraps() {
+        let mut tape: ModArrayTape<u16> = ModArrayTape::with_length(5);
+        *tape = 300;
+        tape.move_ptr(4).unwrap();
+        assert_eq!(tape.inc_ptr().unwrap(), 0);
+        assert_eq!(*tape, 300);
+    }
+
+    #[test]
+    fn move_ptr_wraps() {
+        let mut tape: ModArrayTape = ModArrayTape::with_length(5);
+        tape.move_ptr(-1).unwrap();
+        assert_eq!(tape.ptr(), 4);
+        tape.move_ptr(11).unwrap();
+        assert_eq!(tape.ptr(), 0);
+        tape.move_ptr(-13).unwrap();
+        assert_eq!(tape.ptr(), 2);
+        tape.move_ptr(isize::MIN).unwrap();
+        assert!(tape.ptr() < 5);
+    }
+
+    #[test]
+    fn undoes_move() {
+        let tape: ModArrayTape = ModArrayTape::with_length(5);
+        assert!(tape.undoes_move(4));
+        assert!(tape.undoes_move(-6));
+        assert!(!tape.undoes_move(10));
+    }
+
+    #[test]
+    fn wraps_like_steps() {
+        // `move_ptr` must agree with single steps.
+        for n in -12..12isize {
+            let mut moved: ModArrayTape = ModArrayTape::with_length(5);
+            let mut stepped: ModArrayTape = ModArrayTape::with_length(5);
+            moved.move_ptr(n).unwrap();
+            for _ in 0..n.unsigned_abs() {
+                if n > 0 { stepped.inc_ptr().unwrap(); } else { stepped.dec_ptr().unwrap(); }
+            }
+            assert_eq!(moved.ptr(), stepped.ptr());
+            assert_eq!(moved.touched(), stepped.touched());
+        }
+    }
+}
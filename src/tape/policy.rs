@@ -0,0 +1,100 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// What a `PolicyTape` does when a value goes past the bounds of its cell
+/// type.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ValuePolicy {
+    /// Wrap around to the other bound, like `ModArrayTape`.
+    Wrap,
+    /// Stay at the bound that was reached.
+    Saturate,
+    /// Stop with `Error::ValOverflow` or `Error::ValUnderflow`, like
+    /// `ArrayTape`.
+    #[default]
+    Error,
+}
+
+/// What a `PolicyTape` does when the pointer goes past either end of the
+/// tape.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PointerPolicy {
+    /// Wrap around to the other end of the tape.
+    Wrap,
+    /// Stay at the end that was reached.
+    Clamp,
+    /// Stop with `Error::PtrOverflow` or `Error::PtrUnderflow`, like
+    /// `ArrayTape`.
+    #[default]
+    Error,
+    /// Add cells to whichever end was reached, like `InfiniteTape`. The
+    /// tape has no length.
+    Grow,
+}
+
+impl fmt::Display for ValuePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValuePolicy::Wrap     => write!(f, "wrap"),
+            ValuePolicy::Saturate => write!(f, "saturate"),
+            ValuePolicy::Error    => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for ValuePolicy {
+    type Err = String;
+
+    /// Parse a value policy from the same names it displays as.
+    fn from_str(s: &str) -> Result<ValuePolicy, String> {
+        match s {
+            "wrap"     => Ok(ValuePolicy::Wrap),
+            "saturate" => Ok(ValuePolicy::Saturate),
+            "error"    => Ok(ValuePolicy::Error),
+            _ => Err(format!("Unknown value policy `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for PointerPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PointerPolicy::Wrap  => write!(f, "wrap"),
+            PointerPolicy::Clamp => write!(f, "clamp"),
+            PointerPolicy::Error => write!(f, "error"),
+            PointerPolicy::Grow  => write!(f, "grow"),
+        }
+    }
+}
+
+impl FromStr for PointerPolicy {
+    type Err = String;
+
+    /// Parse a pointer policy from the same names it displays as.
+    fn from_str(s: &str) -> Result<PointerPolicy, String> {
+        match s {
+            "wrap"  => Ok(PointerPolicy::Wrap),
+            "clamp" => Ok(PointerPolicy::Clamp),
+            "error" => Ok(PointerPolicy::Error),
+            "grow"  => Ok(PointerPolicy::Grow),
+            _ => Err(format!("Unknown pointer policy `{}`", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for value in &[ValuePolicy::Wrap, ValuePolicy::Saturate, ValuePolicy::Error] {
+            assert_eq!(value.to_string().parse::<ValuePolicy>(), Ok(*value));
+        }
+        for pointer in &[PointerPolicy::Wrap, PointerPolicy::Clamp, PointerPolicy::Error, PointerPolicy::Grow] {
+            assert_eq!(pointer.to_string().parse::<PointerPolicy>(), Ok(*pointer));
+        }
+        assert!("bogus".parse::<ValuePolicy>().is_err());
+        assert!("saturate".parse::<PointerPolicy>().is_err());
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+use std::ops;
+use super::*;
+
+/// A tape whose behavior at the bounds is chosen when it's made.
+///
+/// What happens when a value goes out of range is set with `values`, and
+/// when the pointer goes off either end of the tape with `pointer`, see
+/// `ValuePolicy` and `PointerPolicy`. Every combination is available, both
+/// default to errors. The tape is `TAPE_LENGTH` cells long unless made with
+/// `with_length`, and like `VecTape` it only allocates the cells the pointer
+/// has reached.
+///
+/// With `PointerPolicy::Grow` indices count from the leftmost cell, as in
+/// `InfiniteTape`, and the cell the pointer started on is at `origin`.
+///
+/// ```
+/// use brainfuck::tape::{PointerPolicy, PolicyTape, Tape, ValuePolicy};
+///
+/// let mut tape: PolicyTape = PolicyTape::with_length(4);
+/// tape.values(ValuePolicy::Saturate).pointer(PointerPolicy::Wrap);
+/// tape.dec_ptr().unwrap();
+/// assert_eq!(tape.ptr(), 3);
+/// tape.dec_val().unwrap();
+/// assert_eq!(*tape, 0);
+/// ```
+pub struct PolicyTape<C: Integer = u8> {
+    cells: VecDeque<C>,
+    ptr: usize,
+    origin: usize,
+    length: usize,
+    values: ValuePolicy,
+    pointer: PointerPolicy,
+}
+
+impl<C: Integer> Default for PolicyTape<C> {
+    fn default() -> Self {
+        PolicyTape::with_length(TAPE_LENGTH)
+    }
+}
+
+impl<C: Integer> PolicyTape<C> {
+    /// Create a tape of `length` cells, which must be at least one.
+    pub fn with_length(length: usize) -> PolicyTape<C> {
+        assert!(length > 0, "a tape needs a cell");
+        let mut cells = VecDeque::new();
+        // Create the first cell.
+        cells.push_back(C::default());
+        PolicyTape {
+            cells,
+            ptr: 0,
+            origin: 0,
+            length,
+            values: ValuePolicy::default(),
+            pointer: PointerPolicy::default(),
+        }
+    }
+
+    /// Use the given policy for values going out of range.
+    pub fn values(&mut self, policy: ValuePolicy) -> &mut Self {
+        self.values = policy;
+        self
+    }
+
+    /// Use the given policy for the pointer going off the tape. A tape which
+    /// grew past its length keeps the cells, and its length grows to fit
+    /// them.
+    pub fn pointer(&mut self, policy: PointerPolicy) -> &mut Self {
+        self.length = self.length.max(self.cells.len());
+        self.pointer = policy;
+        self
+    }
+
+    /// The number of cells in the tape, which has no meaning when it grows.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The index of the cell the pointer started on.
+    pub fn origin(&self) -> usize {
+        self.origin
+    }
+
+    /// Set every cell to `0`, and move the pointer back to the start. The
+    /// length and policies are kept.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.cells.push_back(C::default());
+        self.ptr = 0;
+        self.origin = 0;
+    }
+
+    /// Returns true if the tape has a cell at `index`.
+    fn contains(&self, index: usize) -> bool {
+        index < self.length || self.pointer == PointerPolicy::Grow
+    }
+
+    /// Allocate the cells up to `index`.
+    fn reach(&mut self, index: usize) {
+        if index >= self.cells.len() {
+            self.cells.resize(index + 1, C::default());
+        }
+    }
+}
+
+impl<C: Integer> Tape for PolicyTape<C> {
+    type Cell = C;
+
+    fn is_nice(&self) -> bool {
+        // Saturating values and a clamped pointer quietly change what a
+        // program does, the other policies behave like one of the tapes.
+        self.values != ValuePolicy::Saturate && self.pointer != PointerPolicy::Clamp
+    }
+
+    fn inc_val(&mut self) -> Result<Self::Cell, Error> {
+        self.add_val(1)?;
+        Ok(**self)
+    }
+
+    fn dec_val(&mut self) -> Result<Self::Cell, Error> {
+        self.add_val(-1)?;
+        Ok(**self)
+    }
+
+    fn inc_ptr(&mut self) -> Result<usize, Error> {
+        self.move_ptr(1)?;
+        Ok(self.ptr)
+    }
+
+    fn dec_ptr(&mut self) -> Result<usize, Error> {
+        self.move_ptr(-1)?;
+        Ok(self.ptr)
+    }
+
+    fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    fn get(&self, index: usize) -> Option<C> {
+        if self.contains(index) {
+            // Cells which haven't been allocated yet are `0`.
+            Some(self.cells.get(index).copied().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, index: usize, value: C) -> Result<(), Error> {
+        if !self.contains(index) {
+            return Err(Error::PtrOverflow)
+        }
+        self.reach(index);
+        self.cells[index] = value;
+        Ok(())
+    }
+
+    fn touched(&self) -> Range<usize> {
+        0..self.cells.len()
+    }
+
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        let v = match (self.checked_add_val(n), self.values) {
+            (Some(v), _) => v,
+            (None, ValuePolicy::Wrap) => self.wrapping_add_val(n),
+            (None, ValuePolicy::Saturate) if n > 0 => C::MAX,
+            (None, ValuePolicy::Saturate) => C::MIN,
+            (None, ValuePolicy::Error) if n > 0 => {
+                **self = C::MAX;
+                return Err(Error::ValOverflow)
+            },
+            (None, ValuePolicy::Error) => {
+                **self = C::MIN;
+                return Err(Error::ValUnderflow)
+            },
+        };
+        **self = v;
+        Ok(())
+    }
+
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        let target = self.ptr as i128 + n as i128;
+        let last = self.length - 1;
+        match self.pointer {
+            PointerPolicy::Grow if target < 0 => {
+                let missing = target.unsigned_abs() as usize;
+                for _ in 0..missing {
+                    self.cells.push_front(C::default());
+                }
+                self.origin += missing;
+                self.ptr = 0;
+            },
+            PointerPolicy::Grow => {
+                self.ptr = target as usize;
+                self.reach(self.ptr);
+            },
+            _ if target < 0 => {
+                self.ptr = match self.pointer {
+                    PointerPolicy::Wrap => {
+                        // Every cell is passed on the way around.
+                        self.reach(last);
+                        target.rem_euclid(self.length as i128) as usize
+                    },
+                    PointerPolicy::Error => {
+                        self.ptr = 0;
+                        return Err(Error::PtrUnderflow)
+                    },
+                    _ => 0,
+                };
+            },
+            _ if target > last as i128 => {
+                self.reach(last);
+                self.ptr = match self.pointer {
+                    PointerPolicy::Wrap => (target % self.length as i128) as usize,
+                    PointerPolicy::Error => {
+                        self.ptr = last;
+                        return Err(Error::PtrOverflow)
+                    },
+                    _ => last,
+                };
+            },
+            _ => {
+                self.ptr = target as usize;
+                self.reach(self.ptr);
+            },
+        }
+        Ok(())
+    }
+
+    fn undoes_move(&self, n: isize) -> bool {
+        match self.pointer {
+            PointerPolicy::Wrap => n % self.length as isize != 0,
+            PointerPolicy::Clamp => false,
+            PointerPolicy::Error | PointerPolicy::Grow => n != 0,
+        }
+    }
+}
+
+impl<C: Integer> ops::Deref for PolicyTape<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cells[self.ptr]
+    }
+}
+
+impl<C: Integer> ops::DerefMut for PolicyTape<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.cells[self.ptr]
+    }
+}
+
+#[cfg(test)]
+fn tape<C: Integer>(values: ValuePolicy, pointer: PointerPolicy) -> PolicyTape<C> {
+    let mut tape = PolicyTape::<C>::default();
+    tape.values(values).pointer(pointer);
+    tape
+}
+
+tape_tests!(tests, PolicyTape);
+tape_tests!(tests_i16, PolicyTape<i16>);
+tape_tests!(tests_wrap_wrap, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Wrap, PointerPolicy::Wrap));
+tape_tests!(tests_wrap_clamp, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Wrap, PointerPolicy::Clamp));
+tape_tests!(tests_wrap_error, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Wrap, PointerPolicy::Error));
+tape_tests!(tests_wrap_grow, PolicyTape, None, tape(ValuePolicy::Wrap, PointerPolicy::Grow));
+tape_tests!(tests_saturate_wrap, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Saturate, PointerPolicy::Wrap));
+tape_tests!(tests_saturate_clamp, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Saturate, PointerPolicy::Clamp));
+tape_tests!(tests_saturate_error, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Saturate, PointerPolicy::Error));
+tape_tests!(tests_saturate_grow, PolicyTape, None, tape(ValuePolicy::Saturate, PointerPolicy::Grow));
+tape_tests!(tests_error_wrap, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Error, PointerPolicy::Wrap));
+tape_tests!(tests_error_clamp, PolicyTape, Some(TAPE_LENGTH), tape(ValuePolicy::Error, PointerPolicy::Clamp));
+tape_tests!(tests_error_grow, PolicyTape, None, tape(ValuePolicy::Error, PointerPolicy::Grow));
+
+#[cfg(test)]
+mod policy_tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let mut wrap: PolicyTape = tape(ValuePolicy::Wrap, PointerPolicy::Error);
+        assert_eq!(wrap.dec_val().unwrap(), 255);
+        wrap.add_val(3).unwrap();
+        assert_eq!(*wrap, 2);
+        let mut saturate: PolicyTape<i8> = tape(ValuePolicy::Saturate, PointerPolicy::Error);
+        saturate.add_val(1000).unwrap();
+        assert_eq!(*saturate, i8::MAX);
+        assert_eq!(saturate.inc_val().unwrap(), i8::MAX);
+        saturate.add_val(-1000).unwrap();
+        assert_eq!(*saturate, i8::MIN);
+        let mut error: PolicyTape = tape(ValuePolicy::Error, PointerPolicy::Error);
+        assert!(matches!(error.dec_val(), Err(Error::ValUnderflow)));
+        assert!(matches!(error.add_val(300), Err(Error::ValOverflow)));
+        assert_eq!(*error, 255);
+    }
+
+    #[test]
+    fn pointer_wrap() {
+        let mut tape: PolicyTape = tape(ValuePolicy::Error, PointerPolicy::Wrap);
+        assert_eq!(tape.dec_ptr().unwrap(), TAPE_LENGTH - 1);
+        assert_eq!(tape.touched(), 0..TAPE_LENGTH);
+        assert_eq!(tape.inc_ptr().unwrap(), 0);
+        tape.move_ptr(-1 - 2 * TAPE_LENGTH as isize).unwrap();
+        assert_eq!(tape.ptr(), TAPE_LENGTH - 1);
+        tape.move_ptr(2 * TAPE_LENGTH as isize + 2).unwrap();
+        assert_eq!(tape.ptr(), 1);
+    }
+
+    #[test]
+    fn pointer_clamp() {
+        let mut tape: PolicyTape = PolicyTape::with_length(3);
+        tape.pointer(PointerPolicy::Clamp);
+        assert_eq!(tape.dec_ptr().unwrap(), 0);
+        tape.move_ptr(10).unwrap();
+        assert_eq!(tape.ptr(), 2);
+        assert_eq!(tape.inc_ptr().unwrap(), 2);
+        assert_eq!(tape.touched(), 0..3);
+    }
+
+    #[test]
+    fn pointer_error() {
+        let mut tape: PolicyTape = PolicyTape::with_length(3);
+        assert!(matches!(tape.dec_ptr(), Err(Error::PtrUnderflow)));
+        assert!(matches!(tape.move_ptr(5), Err(Error::PtrOverflow)));
+        assert_eq!(tape.ptr(), 2);
+        assert!(matches!(tape.move_ptr(-5), Err(Error::PtrUnderflow)));
+        assert_eq!(tape.ptr(), 0);
+    }
+
+    #[test]
+    fn pointer_grow() {
+        let mut tape: PolicyTape = PolicyTape::with_length(3);
+        tape.pointer(PointerPolicy::Grow);
+        *tape = 1;
+        tape.move_ptr(-2).unwrap();
+        assert_eq!((tape.ptr(), tape.origin()), (0, 2));
+        tape.move_ptr(5).unwrap();
+        assert_eq!(tape.ptr(), 5);
+        assert_eq!(tape.get(2), Some(1));
+        assert_eq!(tape.touched(), 0..6);
+    }
+
+    #[test]
+    fn nice() {
+        assert!(tape::<u8>(ValuePolicy::Error, PointerPolicy::Error).is_nice());
+        assert!(tape::<u8>(ValuePolicy::Wrap, PointerPolicy::Wrap).is_nice());
+        assert!(tape::<u8>(ValuePolicy::Wrap, PointerPolicy::Grow).is_nice());
+        assert!(!tape::<u8>(ValuePolicy::Saturate, PointerPolicy::Error).is_nice());
+        assert!(!tape::<u8>(ValuePolicy::Wrap, PointerPolicy::Clamp).is_nice());
+    }
+
+    #[test]
+    fn undoes_move() {
+        let mut tape: PolicyTape = PolicyTape::with_length(3);
+        assert!(tape.undoes_move(5));
+        tape.pointer(PointerPolicy::Wrap);
+        assert!(tape.undoes_move(-2));
+        assert!(!tape.undoes_move(3));
+        tape.pointer(PointerPolicy::Clamp);
+        assert!(!tape.undoes_move(1));
+    }
+
+    #[test]
+    fn stop_growing() {
+        let mut tape: PolicyTape = PolicyTape::with_length(3);
+        tape.pointer(PointerPolicy::Grow).move_ptr(4).unwrap();
+        *tape = 1;
+        tape.pointer(PointerPolicy::Wrap);
+        assert_eq!(tape.length(), 5);
+        assert_eq!(tape.cells().collect::<Vec<_>>(), [(0, 0), (1, 0), (2, 0), (3, 0), (4, 1)]);
+        assert_eq!(tape.dump(Format::Decimal), "0   0   0   0   0>  1\n");
+        assert_eq!(tape.inc_ptr().unwrap(), 0);
+    }
+
+    #[test]
+    fn clear() {
+        let mut tape: PolicyTape = PolicyTape::with_length(3);
+        tape.values(ValuePolicy::Wrap).pointer(PointerPolicy::Grow);
+        tape.move_ptr(-2).unwrap();
+        tape.set(4, 9).unwrap();
+        tape.clear();
+        assert_eq!((tape.ptr(), tape.origin(), tape.touched()), (0, 0, 0..1));
+        assert_eq!(tape.get(4), Some(0));
+        assert_eq!(tape.dec_val().unwrap(), 255);
+        assert_eq!(tape.dec_ptr().unwrap(), 0);
+        assert_eq!(tape.origin(), 1);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::mem;
+use std::ops;
+use super::*;
+
+/// A tape which only stores the cells that aren't `0`.
+///
+/// This tape is implemented with a `HashMap<usize, C>`, so its memory
+/// depends on how many cells hold a value rather than how far the pointer
+/// goes. This suits programs which visit a few cells far apart. The tape is
+/// `TAPE_LENGTH` cells long unless made with `with_length`. Values and the
+/// pointer going out of range are errors. Likewise `cells` and `dump` only
+/// go through the cells which aren't `0`, and the current one.
+///
+/// ```
+/// use brainfuck::tape::{SparseTape, Tape};
+///
+/// let mut tape: SparseTape = SparseTape::with_length(1 << 40);
+/// tape.move_ptr(1 << 39).unwrap();
+/// tape.inc_val().unwrap();
+/// assert_eq!(tape.get(1 << 39), Some(1));
+/// ```
+pub struct SparseTape<C: Integer = u8> {
+    cells: HashMap<usize, C>,
+    ptr: usize,
+    length: usize,
+    // The current cell, which is kept out of `cells` until the pointer
+    // leaves it.
+    current: C,
+    // The end of the touched cells.
+    end: usize,
+}
+
+impl<C: Integer> Default for SparseTape<C> {
+    fn default() -> Self {
+        SparseTape::with_length(TAPE_LENGTH)
+    }
+}
+
+impl<C: Integer> SparseTape<C> {
+    /// Create a tape of `length` cells, which must be at least one.
+    pub fn with_length(length: usize) -> SparseTape<C> {
+        assert!(length > 0, "a tape needs a cell");
+        SparseTape {
+            cells: HashMap::new(),
+            ptr: 0,
+            length,
+            current: C::default(),
+            end: 1,
+        }
+    }
+
+    /// The number of cells in the tape.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Move the pointer to `v`, which must be in the tape.
+    fn seek(&mut self, v: usize) {
+        let current = mem::take(&mut self.current);
+        store(&mut self.cells, self.ptr, current);
+        self.current = self.cells.remove(&v).unwrap_or_default();
+        self.ptr = v;
+        self.end = self.end.max(v + 1);
+    }
+}
+
+/// Store a value, leaving out zeros.
+fn store<C: Integer>(cells: &mut HashMap<usize, C>, index: usize, value: C) {
+    if value.is_zero() {
+        cells.remove(&index);
+    } else {
+        cells.insert(index, value);
+    }
+}
+
+impl<C: Integer> Tape for SparseTape<C> {
+    type Cell = C;
+
+    fn is_nice(&self) -> bool {
+        true
+    }
+
+    fn inc_val(&mut self) -> Result<Self::Cell, Error> {
+        match self.checked_add_val(1) {
+            Some(v) => {
+                **self = v;
+                Ok(v)
+            },
+            None => Err(Error::ValOverflow)
+        }
+    }
+
+    fn dec_val(&mut self) -> Result<Self::Cell, Error> {
+        match self.checked_add_val(-1) {
+            Some(v) => {
+                **self = v;
+                Ok(v)
+            },
+            None => Err(Error::ValUnderflow),
+        }
+    }
+
+    fn inc_ptr(&mut self) -> Result<usize, Error> {
+        match self.ptr.checked_add(1) {
+            Some(v) if v < self.length => {
+                self.seek(v);
+                Ok(v)
+            },
+            _ => Err(Error::PtrOverflow),
+        }
+    }
+
+    fn dec_ptr(&mut self) -> Result<usize, Error> {
+        match self.ptr.checked_sub(1) {
+            Some(v) => {
+                self.seek(v);
+                Ok(v)
+            },
+            None => Err(Error::PtrUnderflow),
+        }
+    }
+
+    fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    fn get(&self, index: usize) -> Option<C> {
+        if index == self.ptr {
+            Some(self.current)
+        } else if index < self.length {
+            Some(self.cells.get(&index).copied().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, index: usize, value: C) -> Result<(), Error> {
+        if index >= self.length {
+            return Err(Error::PtrOverflow)
+        }
+        if index == self.ptr {
+            self.current = value;
+        } else {
+            store(&mut self.cells, index, value);
+        }
+        self.end = self.end.max(index + 1);
+        Ok(())
+    }
+
+    fn touched(&self) -> Range<usize> {
+        0..self.end
+    }
+
+    fn cells(&self) -> Cells<'_, Self> {
+        // Only the stored cells, so a far away one isn't reached by going
+        // through every cell before it.
+        let mut indices: Vec<usize> = self.cells.keys().copied().collect();
+        indices.push(self.ptr);
+        indices.sort_unstable();
+        Cells::listed(self, indices)
+    }
+
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        match self.checked_add_val(n) {
+            Some(v) => {
+                **self = v;
+                Ok(())
+            },
+            None if n > 0 => {
+                **self = C::MAX;
+                Err(Error::ValOverflow)
+            },
+            None => {
+                **self = C::MIN;
+                Err(Error::ValUnderflow)
+            },
+        }
+    }
+
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        if n >= 0 {
+            match self.ptr.checked_add(n as usize) {
+                Some(v) if v < self.length => self.seek(v),
+                _ => {
+                    self.seek(self.length - 1);
+                    return Err(Error::PtrOverflow)
+                },
+            }
+        } else {
+            match self.ptr.checked_sub(n.unsigned_abs()) {
+                Some(v) => self.seek(v),
+                None => {
+                    self.seek(0);
+                    return Err(Error::PtrUnderflow)
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: Integer> ops::Deref for SparseTape<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.current
+    }
+}
+
+impl<C: Integer> ops::DerefMut for SparseTape<C> {
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.current
+    }
+}
+
+tape_tests!(tests, SparseTape);
+tape_tests!(tests_u64, SparseTape<u64>);
+tape_tests!(tests_short, SparseTape, Some(100), SparseTape::with_length(100));
+
+#[cfg(test)]
+mod sparse_tests {
+    use super::*;
+
+    #[test]
+    fn only_non_zero() {
+        let mut tape: SparseTape = SparseTape::default();
+        tape.set(100, 1).unwrap();
+        tape.set(200, 2).unwrap();
+        tape.set(100, 0).unwrap();
+        assert_eq!(tape.cells.len(), 1);
+        tape.move_ptr(200).unwrap();
+        *tape = 0;
+        tape.move_ptr(-1).unwrap();
+        assert!(tape.cells.is_empty());
+        assert_eq!(tape.touched(), 0..201);
+    }
+
+    #[test]
+    fn far_cells() {
+        let mut tape: SparseTape = SparseTape::with_length(1 << 40);
+        tape.set(1 << 39, 7).unwrap();
+        tape.move_ptr(3).unwrap();
+        assert_eq!(tape.touched(), 0..(1 << 39) + 1);
+        assert_eq!(tape.cells().collect::<Vec<_>>(), [(3, 0), (1 << 39, 7)]);
+        assert_eq!(tape.dump(Format::Decimal), "\
+            000000000000   0   0   0>  0   0   0   0   0   0   0   0   0   0   0   0   0\n\
+            549755813888   7\n");
+    }
+
+    #[test]
+    fn length() {
+        let mut tape: SparseTape = SparseTape::with_length(10);
+        assert_eq!(tape.length(), 10);
+        assert!(matches!(tape.move_ptr(12), Err(Error::PtrOverflow)));
+        assert_eq!(tape.ptr(), 9);
+        assert!(matches!(tape.inc_ptr(), Err(Error::PtrOverflow)));
+        assert_eq!(tape.get(10), None);
+        assert!(matches!(tape.move_ptr(-12), Err(Error::PtrUnderflow)));
+        assert_eq!(tape.ptr(), 0);
+    }
+}
@@ -3,35 +3,49 @@ use super::*;
 
 /// A tape with dynamically allocated cells.
 ///
-/// This tape is implemented with a `Vec<u8>` so it only uses memory it
-/// needs. The cells are of type `u8`, and the tape's length is forced to
-/// be no greater than `TAPE_LENGTH` so this tape is *nice*.
-pub struct VecTape {
-    cells: Vec<u8>,
+/// This tape is implemented with a `Vec<C>` so it only uses memory it
+/// needs. The cells are of type `u8` by default, and the tape is
+/// `TAPE_LENGTH` cells long unless made with `with_length`. Values and the
+/// pointer going out of range are errors.
+pub struct VecTape<C: Integer = u8> {
+    cells: Vec<C>,
     ptr: usize,
+    length: usize,
 }
 
-impl Default for VecTape {
+impl<C: Integer> Default for VecTape<C> {
     fn default() -> Self {
-        let mut vec = Vec::new();
-        // Create the first cell.
-        vec.push(0);
+        VecTape::with_length(TAPE_LENGTH)
+    }
+}
+
+impl<C: Integer> VecTape<C> {
+    /// Create a tape of `length` cells, which must be at least one.
+    pub fn with_length(length: usize) -> VecTape<C> {
+        assert!(length > 0, "a tape needs a cell");
         VecTape {
-            cells: vec,
+            // Create the first cell.
+            cells: vec![C::default()],
             ptr: 0,
+            length,
         }
     }
+
+    /// The number of cells in the tape.
+    pub fn length(&self) -> usize {
+        self.length
+    }
 }
 
-impl Tape for VecTape {
-    type Cell = u8;
+impl<C: Integer> Tape for VecTape<C> {
+    type Cell = C;
 
-    fn is_nice() -> bool {
+    fn is_nice(&self) -> bool {
         true
     }
 
     fn inc_val(&mut self) -> Result<Self::Cell, Error> {
-        match self.checked_add(1) {
+        match self.checked_add_val(1) {
             Some(v) => {
                 **self = v;
                 Ok(v)
@@ -41,7 +55,7 @@ impl Tape for VecTape {
     }
 
     fn dec_val(&mut self) -> Result<Self::Cell, Error> {
-        match self.checked_sub(1) {
+        match self.checked_add_val(-1) {
             Some(v) => {
                 **self = v;
                 Ok(v)
@@ -52,10 +66,10 @@ impl Tape for VecTape {
 
     fn inc_ptr(&mut self) -> Result<usize, Error> {
         match self.ptr.checked_add(1) {
-            Some(v) if v < TAPE_LENGTH => {
+            Some(v) if v < self.length => {
                 if v >= self.cells.len() {
                     // Add another cell dynamically.
-                    self.cells.push(0);
+                    self.cells.push(C::default());
                 }
                 self.ptr = v;
                 Ok(v)
@@ -66,27 +80,94 @@ impl Tape for VecTape {
 
     fn dec_ptr(&mut self) -> Result<usize, Error> {
         match self.ptr.checked_sub(1) {
-            Some(v) if v < TAPE_LENGTH => {
+            Some(v) => {
                 self.ptr = v;
                 Ok(v)
             },
             _ => Err(Error::PtrUnderflow),
         }
     }
+
+    fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    fn get(&self, index: usize) -> Option<C> {
+        if index < self.length {
+            // Cells which haven't been allocated yet are `0`.
+            Some(self.cells.get(index).copied().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, index: usize, value: C) -> Result<(), Error> {
+        if index >= self.length {
+            return Err(Error::PtrOverflow)
+        }
+        if index >= self.cells.len() {
+            self.cells.resize(index + 1, C::default());
+        }
+        self.cells[index] = value;
+        Ok(())
+    }
+
+    fn touched(&self) -> Range<usize> {
+        0..self.cells.len()
+    }
+
+    fn add_val(&mut self, n: isize) -> Result<(), Error> {
+        match self.checked_add_val(n) {
+            Some(v) => {
+                **self = v;
+                Ok(())
+            },
+            None if n > 0 => {
+                **self = C::MAX;
+                Err(Error::ValOverflow)
+            },
+            None => {
+                **self = C::MIN;
+                Err(Error::ValUnderflow)
+            },
+        }
+    }
+
+    fn move_ptr(&mut self, n: isize) -> Result<(), Error> {
+        let v = self.ptr as isize + n;
+        if v >= self.length as isize {
+            self.cells.resize(self.length, C::default());
+            self.ptr = self.length - 1;
+            Err(Error::PtrOverflow)
+        } else if v < 0 {
+            self.ptr = 0;
+            Err(Error::PtrUnderflow)
+        } else {
+            let v = v as usize;
+            if v >= self.cells.len() {
+                self.cells.resize(v + 1, C::default());
+            }
+            self.ptr = v;
+            Ok(())
+        }
+    }
 }
 
-impl ops::Deref for VecTape {
-    type Target = u8;
+impl<C: Integer> ops::Deref for VecTape<C> {
+    type Target = C;
 
     fn deref(&self) -> &Self::Target {
         &self.cells[self.ptr]
     }
 }
 
-impl ops::DerefMut for VecTape {
-    fn deref_mut(&mut self) -> &mut u8 {
+impl<C: Integer> ops::DerefMut for VecTape<C> {
+    fn deref_mut(&mut self) -> &mut C {
         &mut self.cells[self.ptr]
     }
 }
 
-tape_tests!(VecTape);
+tape_tests!(tests, VecTape);
+tape_tests!(tests_u16, VecTape<u16>);
+tape_tests!(tests_i32, VecTape<i32>);
+tape_tests!(tests_short, VecTape, Some(100), VecTape::with_length(100));
@@ -1,7 +1,13 @@
 extern crate brainfuck;
 
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
 use brainfuck::*;
+use brainfuck::codegen::C;
 use brainfuck::program::Program;
+use brainfuck::tape::Tape;
 
 macro_rules! load_and_run {
     ($name:ident, $path:expr) => {
@@ -37,7 +43,6 @@ load_and_run!(bf_392quine,        "fixtures/392quine.b");
 load_and_run!(bf_400quine,        "fixtures/400quine.b");
 load_and_run!(bf_540quine,        "fixtures/540quine.b");
 load_and_run!(bf_dquine,          "fixtures/dquine.b");
-load_and_run!(bf_utm,             "fixtures/utm.b");
 load_and_run!(bf_dvorak,          "fixtures/dvorak.b");
 load_and_run!(bf_rot13,           "fixtures/rot13.b");
 load_and_run!(bf_wc,              "fixtures/wc.b");
@@ -75,3 +80,446 @@ fn bf_dbfi() {
     }
     assert_eq!(String::from_utf8(writer).unwrap(), "1");
 }
+
+#[test]
+fn bf_utm() {
+    let mut reader = "b1b1bbb1c1c11111d".as_bytes();
+    let mut writer = Vec::<u8>::new();
+    let program = Program::from_file("fixtures/utm.b").unwrap();
+    {
+        let mut interp = Interpreter::<tape::ModArrayTape>::new(program, &mut reader, &mut writer);
+        interp.run().unwrap();
+    }
+    assert_eq!(String::from_utf8(writer).unwrap(), "1c11111\n");
+}
+
+#[test]
+fn bf_tests_eof() {
+    for &(eof, expected) in &[(Eof::Unchanged, "LK\nLK\n"),
+                              (Eof::Zero,      "LB\nLB\n"),
+                              (Eof::AllOnes,   "LA\nLA\n")] {
+        let mut reader = "\n".as_bytes();
+        let mut writer = Vec::<u8>::new();
+        // The first program in `fixtures/tests.b` probes the EOF convention.
+        let program = Program::parse(">,>+++++++++,>+++++++++++[<++++++<++++++<+>>>-]<<.>.<<-.>.>.<<.").unwrap();
+        {
+            let mut interp = Interpreter::<tape::ModArrayTape>::new(program, &mut reader, &mut writer);
+            interp.on_eof(eof);
+            interp.run().unwrap();
+        }
+        assert_eq!(String::from_utf8(writer).unwrap(), expected);
+    }
+}
+
+#[test]
+fn bf_fib_cycle_limit() {
+    let mut reader = "".as_bytes();
+    let mut writer = Vec::<u8>::new();
+    let program = Program::from_file("fixtures/fib.b").unwrap();
+    {
+        let mut interp = Interpreter::<tape::VecTape>::new(program, &mut reader, &mut writer);
+        interp.cycle_limit(Some(100_000));
+        match interp.run() {
+            Err(Error::CycleLimit) => assert_eq!(interp.cycles(), 100_000),
+            r => panic!("expected Error::CycleLimit, got {:?}", r),
+        }
+    }
+    assert!(String::from_utf8(writer).unwrap().starts_with("0\n1\n1\n2\n3\n5\n8\n"));
+}
+
+#[test]
+fn optimized_matches() {
+    for &(path, input) in &[("fixtures/utm.b", "b1b1bbb1c1c11111d"),
+                            ("fixtures/collatz.b", "27\n"),
+                            ("fixtures/rot13.b", "~mlk zyx"),
+                            ("fixtures/numwarp.b", "3.14159"),
+                            ("fixtures/squares.b", "")] {
+        let run = |optimize| {
+            let mut reader = input.as_bytes();
+            let mut writer = Vec::<u8>::new();
+            let program = Program::from_file(path).unwrap();
+            let cycles = {
+                let mut interp = Interpreter::<tape::ModArrayTape>::new(program, &mut reader, &mut writer);
+                interp.optimize(optimize).run().unwrap();
+                interp.cycles()
+            };
+            (writer, cycles)
+        };
+        assert_eq!(run(false), run(true), "{}", path);
+    }
+}
+
+/// Transpile the program at the given path to C, build it with the system's
+/// C compiler and run it with the given input. Returns `None` when there is
+/// no C compiler to use.
+fn run_c(path: &str, input: &str, eof: Eof) -> Option<Vec<u8>> {
+    let program = Program::from_file(path).unwrap();
+    let name = path.replace("/", "_");
+    let source = env::temp_dir().join(format!("brainfuck_{}_{}.c", name, eof));
+    let binary = env::temp_dir().join(format!("brainfuck_{}_{}", name, eof));
+    fs::write(&source, C::default().on_eof(eof).transpile(&program)).unwrap();
+    let status = Command::new("cc").arg("-O1").arg("-o").arg(&binary).arg(&source).status().ok()?;
+    assert!(status.success(), "compiling {}", path);
+    let mut child = Command::new(&binary)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "running {}", path);
+    Some(output.stdout)
+}
+
+#[test]
+fn c_matches() {
+    for &(path, input, eof) in &[("fixtures/helloworld.b", "", Eof::Unchanged),
+                                 ("fixtures/utm.b", "b1b1bbb1c1c11111d", Eof::Unchanged),
+                                 ("fixtures/collatz.b", "27\n", Eof::Unchanged),
+                                 ("fixtures/rot13.b", "~mlk zyx", Eof::Unchanged),
+                                 ("fixtures/numwarp.b", "3.14159", Eof::Zero),
+                                 ("fixtures/squares.b", "", Eof::AllOnes),
+                                 ("fixtures/dbfi.b", ",.!1", Eof::Unchanged)] {
+        let compiled = match run_c(path, input, eof) {
+            Some(output) => output,
+            None => return,
+        };
+        let mut reader = input.as_bytes();
+        let mut writer = Vec::<u8>::new();
+        let program = Program::from_file(path).unwrap();
+        {
+            let mut interp = Interpreter::<tape::ModArrayTape>::new(program, &mut reader, &mut writer);
+            interp.on_eof(eof).run().unwrap();
+        }
+        assert_eq!(writer, compiled, "{}", path);
+    }
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn jit_matches() {
+    use brainfuck::jit::Jit;
+
+    for &(path, input, eof) in &[("fixtures/helloworld.b", "", Eof::Unchanged),
+                                 ("fixtures/utm.b", "b1b1bbb1c1c11111d", Eof::Unchanged),
+                                 ("fixtures/collatz.b", "27\n", Eof::Unchanged),
+                                 ("fixtures/rot13.b", "~mlk zyx", Eof::Unchanged),
+                                 ("fixtures/numwarp.b", "3.14159", Eof::Zero),
+                                 ("fixtures/squares.b", "", Eof::AllOnes),
+                                 ("fixtures/392quine.b", "", Eof::Unchanged),
+                                 ("fixtures/dquine.b", "", Eof::Unchanged),
+                                 ("fixtures/dbf2c.b", "+>", Eof::Unchanged),
+                                 ("fixtures/dbfi.b", ",.!1", Eof::Unchanged)] {
+        let run = |jit: bool| {
+            let mut reader = input.as_bytes();
+            let mut writer = Vec::<u8>::new();
+            let program = Program::from_file(path).unwrap();
+            if jit {
+                Jit::new(program, &mut reader, &mut writer).on_eof(eof).run().unwrap();
+            } else {
+                let mut interp = Interpreter::<tape::ModArrayTape>::new(program, &mut reader, &mut writer);
+                interp.on_eof(eof).run().unwrap();
+            }
+            writer
+        };
+        assert_eq!(run(false), run(true), "{}", path);
+    }
+}
+
+#[test]
+fn interleaved() {
+    let mut hello = Vec::<u8>::new();
+    let mut squares = Vec::<u8>::new();
+    {
+        let mut interps = vec![Interpreter::<tape::ArrayTape>::default(),
+                               Interpreter::<tape::ArrayTape>::default()];
+        interps[0].load(Program::from_file("fixtures/helloworld.b").unwrap())
+            .write_to(&mut hello);
+        interps[1].load(Program::from_file("fixtures/squares.b").unwrap())
+            .write_to(&mut squares);
+        // Take turns until both programs are done.
+        while !interps.is_empty() {
+            interps.retain_mut(|i| match i.run_for(1000) {
+                Status::Running => true,
+                Status::Halted => false,
+                Status::Error(e) => panic!("{}", e),
+            });
+        }
+    }
+    assert_eq!(hello, b"Hello World!\n");
+    assert!(squares.starts_with(b"0\n1\n4\n9\n"));
+}
+
+#[test]
+fn final_tape() {
+    let program = Program::parse("++++++++[>++++>++++++++<<-]>>+").unwrap();
+    let mut interp = Interpreter::<tape::VecTape>::default();
+    interp.load(program).run().unwrap();
+    let cells: Vec<u8> = interp.tape().cells().map(|(_, v)| v).collect();
+    assert_eq!(cells, [0, 32, 65]);
+    assert_eq!(interp.tape().dump(tape::Format::Decimal), "0   0  32> 65\n");
+}
+
+#[test]
+fn wide_cells() {
+    let mut writer = Vec::<u8>::new();
+    {
+        let program = Program::from_file("fixtures/helloworld.b").unwrap();
+        let mut interp = Interpreter::<tape::ModArrayTape<u32>>::default();
+        interp.load(program).write_to(&mut writer).run().unwrap();
+    }
+    assert_eq!(writer, b"Hello World!\n");
+}
+
+fn factorials<T: Tape + Default>(cycles: u64) -> Vec<u8> {
+    let mut writer = Vec::<u8>::new();
+    {
+        let program = Program::from_file("fixtures/factorial.b").unwrap();
+        let mut interp = Interpreter::<T>::default();
+        interp.load(program).write_to(&mut writer).cycle_limit(Some(cycles));
+        // The program never ends.
+        assert!(matches!(interp.run(), Err(Error::CycleLimit)));
+    }
+    writer
+}
+
+#[test]
+fn big_cells() {
+    let big = factorials::<tape::BigTape>(200_000);
+    assert!(big.starts_with(b"1\n1\n2\n6\n24\n120\n"));
+    assert_eq!(big, factorials::<tape::ModArrayTape>(200_000));
+}
+
+#[test]
+fn left_of_start() {
+    let source = "<++++++++[>++++++++<-]>+.";
+    let mut writer = Vec::<u8>::new();
+    {
+        let program = Program::parse(source).unwrap();
+        let mut interp = Interpreter::<tape::InfiniteTape>::default();
+        interp.load(program).write_to(&mut writer).run().unwrap();
+        assert_eq!(interp.tape().origin(), 1);
+    }
+    assert_eq!(writer, b"A");
+    let program = Program::parse(source).unwrap();
+    let mut interp = Interpreter::<tape::VecTape>::default();
+    assert!(interp.load(program).run().is_err());
+}
+
+#[test]
+fn far_cells() {
+    // The second program in `fixtures/tests.b`, which goes to cell 30000.
+    let program = Program::parse("++++[>++++++<-]>[>+++++>+++++++<<-]>>++++<[[>[[>>+<<-]<]>>>-]>-[>+>+<<-]>]
+                                  +++++[>+++++++<<++>-]>.<<.").unwrap();
+    let mut writer = Vec::<u8>::new();
+    {
+        let mut interp = Interpreter::<tape::SparseTape>::default();
+        interp.load(program)
+            .use_tape(tape::SparseTape::with_length(30001))
+            .optimize(true)
+            .cycle_limit(None)
+            .write_to(&mut writer)
+            .run()
+            .unwrap();
+    }
+    assert_eq!(writer, b"#\n");
+}
+
+#[test]
+fn tape_length() {
+    let mut interp = Interpreter::<tape::ArrayTape>::default();
+    interp.load(Program::parse(">>>>+").unwrap())
+        .use_tape(tape::ArrayTape::with_length(5));
+    interp.run().unwrap();
+    assert_eq!(interp.tape().touched(), 0..5);
+    let mut interp = Interpreter::<tape::ArrayTape>::default();
+    interp.load(Program::parse(">>>>>+").unwrap())
+        .use_tape(tape::ArrayTape::with_length(5));
+    let e = interp.run().unwrap_err();
+    assert!(matches!(e.cause(), Error::Tape(tape::Error::PtrOverflow)));
+}
+
+#[test]
+fn policies() {
+    use brainfuck::tape::{PointerPolicy, PolicyTape, ValuePolicy};
+
+    for &optimize in &[false, true] {
+        let mut writer = Vec::<u8>::new();
+        {
+            let mut tape: PolicyTape = PolicyTape::with_length(4);
+            tape.values(ValuePolicy::Saturate).pointer(PointerPolicy::Wrap);
+            let mut interp = Interpreter::<PolicyTape>::default();
+            interp.load(Program::parse("<--.>>>>+++.").unwrap())
+                .use_tape(tape)
+                .write_to(&mut writer)
+                .optimize(optimize)
+                .run()
+                .unwrap();
+            assert_eq!(interp.tape().ptr(), 3);
+        }
+        assert_eq!(writer, [0, 3]);
+    }
+}
+
+#[test]
+fn mod_pointer_wraps() {
+    for &optimize in &[false, true] {
+        let mut writer = Vec::<u8>::new();
+        {
+            let mut interp = Interpreter::<tape::ModArrayTape>::default();
+            interp.load(Program::parse("<+++[>+<-]>.<<.").unwrap())
+                .use_tape(tape::ModArrayTape::with_length(10))
+                .write_to(&mut writer)
+                .optimize(optimize)
+                .run()
+                .unwrap();
+            assert_eq!(interp.tape().ptr(), 8);
+        }
+        assert_eq!(writer, [3, 0]);
+    }
+}
+
+/// Run the binary with the given arguments and input, returning its exit
+/// code, output and errors.
+fn bin(args: &[&str], input: &str) -> (Option<i32>, Vec<u8>, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brainfuck"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    (output.status.code(), output.stdout, stderr)
+}
+
+#[test]
+fn exit_codes() {
+    let run = |args: &[&str]| {
+        let (code, _, stderr) = bin(args, "");
+        (code, stderr)
+    };
+    assert_eq!(run(&["-e", "+."]).0, Some(0));
+    assert_eq!(run(&["--eof=bogus", "-e", "+"]).0, Some(1));
+    assert_eq!(run(&["-e", "+["]).0, Some(2));
+    let (code, stderr) = run(&["--eof=error", "-e", "+\n,"]);
+    assert_eq!(code, Some(3));
+    assert_eq!(stderr, "error: Read past the end of input at 1 (<program>:2:1) `,`, \
+                        after 1 cycles with the pointer at 0\n");
+    assert_eq!(run(&["--cycle-limit=3", "-e", "+++++"]).0, Some(4));
+    assert_eq!(run(&["--cycle-limit=3", "-e", "+++"]).0, Some(0));
+    assert_eq!(run(&["--cycle-limit=3", "-O", "-e", "+++"]).0, Some(0));
+    assert_eq!(run(&["--cycle-limit=0", "-e", ""]).0, Some(0));
+    assert_eq!(run(&["fixtures/missing.b"]).0, Some(5));
+    assert_eq!(run(&["check", "-e", "+["]).0, Some(2));
+    assert_eq!(run(&["compile", "--emit=bogus", "-e", "+"]).0, Some(1));
+}
+
+#[test]
+fn subcommands() {
+    let hello = b"Hello World!\n";
+    assert_eq!(bin(&["fixtures/helloworld.b"], "").1, hello);
+    assert_eq!(bin(&["run", "-O", "fixtures/helloworld.b"], "").1, hello);
+    // Prints whether 16 * 16 fits in a cell.
+    let fits = "++++++++++++++++[>++++++++++++++++<-]>[>+<[-]]>.";
+    assert_eq!(bin(&["run", "-e", fits], "").1, [0]);
+    assert_eq!(bin(&["run", "--cell-width=16", "-e", fits], "").1, [1]);
+    assert_eq!(bin(&["compile", "--emit=asl", "-e", "a+b[-]"], "").1, b"+[-]\n");
+    assert_eq!(bin(&["fmt", "--width=8", "-e", "+[>[-]<-]"], "").1,
+               b"+\n[\n    >[-]<-\n]\n");
+    let (code, stdout, stderr) = bin(&["check", "-e", "[-]+"], "");
+    assert_eq!((code, stdout), (Some(0), vec![]));
+    assert!(stderr.starts_with("warning: Loop never runs"), "{}", stderr);
+    // The tape is kept from one line to the next, and errors don't end it.
+    let (code, stdout, _) = bin(&["repl"], "+++\n[\n.\n");
+    let stdout = String::from_utf8(stdout).unwrap();
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("Missing closing bracket"), "{}", stdout);
+    assert!(stdout.ends_with("> \x03> \n"), "{}", stdout);
+}
+
+#[test]
+fn repl() {
+    let run = |args: &[&str], input: &str| {
+        let (code, stdout, _) = bin(args, input);
+        (code, String::from_utf8(stdout).unwrap())
+    };
+    // The semantics of the chosen tape are kept, until they're changed.
+    assert_eq!(run(&["repl", "--tape=vec"], "-\n:p wrap\n<+\n:t 0\n:q\n+\n"),
+               (Some(0), "> Error: Tape value underflowed at 0 (<line>:1:1) `-`, after 0 cycles \
+                          with the pointer at 0\n> > > [29999:1]\n> ".to_owned()));
+    assert_eq!(run(&["repl", "--tape=infinite"], ":load fixtures/helloworld.b\n:reset\n<+\n:t 1\n").1,
+               "> Hello World!\n> > > [0:1] 1:0\n> \n");
+    assert_eq!(run(&["repl", "--tape=big"], "").0, Some(1));
+}
+
+#[test]
+fn tape_choice() {
+    let run = |args: &[&str]| {
+        let (code, stdout, _) = bin(args, "");
+        (code, stdout)
+    };
+    let underflow = ["-e", "+--."];
+    assert_eq!(run(&underflow), (Some(0), vec![255]));
+    assert_eq!(run(&[&["--tape=mod-array"], &underflow[..]].concat()), (Some(0), vec![255]));
+    for tape in &["vec", "array", "infinite", "sparse", "policy"] {
+        let tape = format!("--tape={}", tape);
+        assert_eq!(run(&[&[&tape[..]], &underflow[..]].concat()), (Some(3), vec![]));
+    }
+    assert_eq!(run(&["--tape=policy", "--values=saturate", "-e", "+--."]), (Some(0), vec![0]));
+    assert_eq!(run(&["--tape=infinite", "-e", "+<+>."]), (Some(0), vec![1]));
+    assert_eq!(run(&["--tape=big", "-e", "+<."]).0, Some(3));
+    assert_eq!(run(&["--tape=big", "--cell-width=16", "-e", "++++++++++++++++[>++++++++++++++++<-]>[>+<[-]]>."]),
+               (Some(0), vec![1]));
+    // A clamped pointer can't undo its moves, so `-O` can't replace the loop.
+    let clamp = ["--tape=policy", "--pointer=clamp", "--values=wrap", "--tape-length=1",
+                 "--cycle-limit=100", "-e", "+[->+<]>."];
+    assert_eq!(run(&clamp), (Some(4), vec![]));
+    assert_eq!(run(&[&["-O"], &clamp[..]].concat()), (Some(4), vec![]));
+    // The JIT wraps the pointer like the default tape.
+    if cfg!(feature = "jit") {
+        assert_eq!(run(&["--jit", "-e", "+<+."]), (Some(0), vec![1]));
+        assert_eq!(run(&["--jit", "--tape=array", "-e", "+"]).0, Some(1));
+    }
+    assert_eq!(run(&["--tape=bogus", "-e", "+"]).0, Some(1));
+    assert_eq!(run(&["--tape=policy", "--pointer=bogus", "-e", "+"]).0, Some(1));
+}
+
+#[test]
+fn profile() {
+    let (code, stdout, stderr) = bin(&["profile", "--top=1", "-e", "++[>+++[-]<-]>."], "");
+    assert_eq!((code, stdout), (Some(0), vec![0]));
+    assert_eq!(stderr, "\
+33 | ++[>+++[-]<-]>.
+
+cycles      % iterations  entries  location       loop
+    29  87.9%          2        1  <program>:1:3  [>+++[-]<-]
+");
+    // The profile is printed before the error.
+    let (code, _, stderr) = bin(&["profile", "--tape=vec", "-e", "+[>+]"], "");
+    assert_eq!(code, Some(3));
+    assert!(stderr.starts_with("89999 | +[>+]\n"), "{}", stderr);
+    assert!(stderr.ends_with("`>`, after 89999 cycles with the pointer at 29999\n"), "{}", stderr);
+}
+
+#[test]
+fn profile_report() {
+    let (code, stdout, stderr) = bin(&["profile", "--format=json", "-e", "+[-]."], "");
+    assert_eq!((code, stdout), (Some(0), vec![0]));
+    assert!(stderr.starts_with(r#"{"schema":1,"program":"<program>","cycles":5,"#), "{}", stderr);
+    assert!(stderr.ends_with(r#""loops":[{"start":1,"end":3,"line":1,"column":2,"entries":1,"iterations":1,"cycles":3}]}
+"#), "{}", stderr);
+    let path = env::temp_dir().join("brainfuck_profile_report.csv");
+    let (code, _, stderr) = bin(&["profile", "--format=csv", "--report", path.to_str().unwrap(),
+                                  "fixtures/helloworld.b"], "");
+    assert_eq!((code, stderr), (Some(0), String::new()));
+    let csv = fs::read_to_string(&path).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("schema,program,kind,pc,end,instruction,line,column,count,entries,iterations,cycles"));
+    assert_eq!(lines.next(), Some("1,fixtures/helloworld.b,program,,,,,,,,,390"));
+    assert_eq!(lines.last(), Some("1,fixtures/helloworld.b,loop,10,41,,1,11,,1,10,311"));
+    assert_eq!(bin(&["profile", "--format=xml", "-e", "+"], "").0, Some(1));
+}